@@ -0,0 +1,237 @@
+use crate::Span;
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Underlines a span of the source. The primary labels say where the problem is, secondary ones
+/// point at related code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// An error or warning about some source, rendered the way rustc reports them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Renders the report with the lines of `source` the labels point at. `path` names the file
+    /// in the location line, `color` adds ANSI escapes for a terminal.
+    ///
+    /// ```text
+    /// error: expected `=` in variable declaration
+    ///  --> main.monk:1:7
+    ///   |
+    /// 1 | let x 5
+    ///   |       ^ expected `=`
+    /// ```
+    pub fn render(&self, source: &str, path: &str, color: bool) -> String {
+        let paint = |text: &str, style: &str| match color {
+            true => format!("{style}{text}{RESET}"),
+            false => text.into(),
+        };
+        let (name, style) = match self.severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+        };
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{}{}",
+            paint(name, style),
+            paint(&format!(": {}", self.message), BOLD)
+        )
+        .unwrap();
+
+        let mut labels: Vec<_> = self.labels.iter().collect();
+        labels.sort_by_key(|label| (label.span.line, label.span.col));
+        let width = labels
+            .iter()
+            .map(|label| label.span.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let gutter = paint(&format!("{:width$} |", ""), BLUE);
+
+        let location = self
+            .labels
+            .iter()
+            .find(|label| label.primary)
+            .or(labels.first().copied());
+        if let Some(label) = location {
+            let arrow = paint(&format!("{:width$}-->", ""), BLUE);
+            writeln!(out, "{arrow} {path}:{}:{}", label.span.line, label.span.col).unwrap();
+            writeln!(out, "{gutter}").unwrap();
+        }
+        let mut previous: Option<usize> = None;
+        for label in labels {
+            let line = label.span.line;
+            if previous.is_some_and(|previous| line > previous + 1) {
+                writeln!(out, "{}", paint("...", BLUE)).unwrap();
+            }
+            let (text, prefix, len) = snippet(source, label.span);
+            if previous != Some(line) {
+                let number = paint(&format!("{line:width$} |"), BLUE);
+                writeln!(out, "{number} {text}").unwrap();
+            }
+            previous = Some(line);
+
+            let (marker, style) = match label.primary {
+                true => ('^', style),
+                false => ('-', BLUE),
+            };
+            let mut underline: String = std::iter::repeat_n(marker, len.max(1)).collect();
+            if !label.message.is_empty() {
+                underline = format!("{underline} {}", label.message);
+            }
+            writeln!(out, "{gutter} {prefix}{}", paint(&underline, style)).unwrap();
+        }
+
+        let notes = self.notes.iter().map(|note| (paint("note", BOLD), note));
+        let help = self.help.iter().map(|help| (paint("help", CYAN), help));
+        let eq = paint(&format!("{:width$} =", ""), BLUE);
+        for (kind, text) in notes.chain(help) {
+            writeln!(out, "{eq} {kind}: {text}").unwrap();
+        }
+        out
+    }
+}
+
+/// The line `span` starts on, the whitespace that lines up with its start, and how many characters
+/// of it fall on that line.
+fn snippet(source: &str, span: Span) -> (&str, String, usize) {
+    let start = floor_char_boundary(source, span.start.min(source.len()));
+    let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |index| start + index);
+    let text = source[line_start..line_end].trim_end_matches('\r');
+    // Tabs stay tabs, so the markers line up however wide they are displayed.
+    let prefix = source[line_start..start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let end = floor_char_boundary(source, span.end.clamp(start, line_end));
+    (text, prefix, source[start..end].chars().count())
+}
+
+fn floor_char_boundary(source: &str, mut index: usize) -> usize {
+    while !source.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Diagnostic, Span};
+
+    #[test]
+    fn test_render() {
+        let source = "let x = 1\nlet y = \"a\"\n\nx + y\n";
+        let diagnostic = Diagnostic::error("cannot add int and string")
+            .with_label(Span::new(23, 28, 4, 1), "")
+            .with_secondary(Span::new(4, 5, 1, 5), "int")
+            .with_secondary(Span::new(18, 21, 2, 9), "string")
+            .with_note("`+` adds numbers or joins strings")
+            .with_help("use interpolation: \"{x}{y}\"");
+        assert_eq!(
+            diagnostic.render(source, "main.monk", false),
+            "error: cannot add int and string
+ --> main.monk:4:1
+  |
+1 | let x = 1
+  |     - int
+2 | let y = \"a\"
+  |         --- string
+...
+4 | x + y
+  | ^^^^^
+  = note: `+` adds numbers or joins strings
+  = help: use interpolation: \"{x}{y}\"
+"
+        );
+
+        // Spans past the end of a line stop there; empty ones still get a marker.
+        let diagnostic = Diagnostic::warning("odd")
+            .with_label(Span::new(4, 14, 1, 5), "here")
+            .with_label(Span::new(21, 21, 2, 12), "and here");
+        assert_eq!(
+            diagnostic.render(source, "main.monk", false),
+            "warning: odd
+ --> main.monk:1:5
+  |
+1 | let x = 1
+  |     ^^^^^ here
+2 | let y = \"a\"
+  |            ^ and here
+"
+        );
+        let colored = diagnostic.render(source, "main.monk", true);
+        assert!(colored.starts_with("\x1b[1;33mwarning\x1b[0m"));
+    }
+}
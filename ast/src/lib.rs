@@ -1,40 +1,166 @@
+use std::fmt;
 use std::path::PathBuf;
 
+pub use diagnostic::{Diagnostic, Label, Severity};
+pub use symbol::Symbol;
+
+mod diagnostic;
+mod symbol;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, col: usize) -> Self {
+        Self {
+            start,
+            end,
+            line,
+            col,
+        }
+    }
+
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+            line: self.line,
+            col: self.col,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Moves the span by `offset` bytes and `lines` lines, for when text in front of it changed.
+    pub fn shift(&mut self, offset: isize, lines: isize) {
+        self.start = self.start.saturating_add_signed(offset);
+        self.end = self.end.saturating_add_signed(offset);
+        self.line = self.line.saturating_add_signed(lines);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Ast {
     pub name: Option<String>,
     pub path: Option<PathBuf>,
     pub prog: Vec<Stmt>,
 }
 
-pub enum Stmt {
-    Let(String, Option<Type>, Expr),
-    Func(String, Params, Option<Type>, Expr),
-    For(Expr, Box<Stmt>),
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+    pub doc: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StmtKind {
+    Let(Symbol, Mutability, Option<Type>, Expr),
+    Func(Symbol, Params, Option<Type>, Expr),
+    For(Symbol, Expr, Box<Stmt>),
     While(Expr, Box<Stmt>),
-    Match(Expr, Cases),
+    Break,
+    Continue,
+    /// `target = value`, or with the operation for `target += value` and the like.
+    Assign(Expr, Option<Operation>, Expr),
     Expr(Expr),
+    /// Stands in for a statement that failed to parse. The error is reported separately.
+    Error,
 }
 
-pub enum Expr {
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprKind {
     Value(Value),
-    Call(String, Vec<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
     Binary(Box<Expr>, Operation, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    /// `start .. end` or `start ..= end`, with an optional `step`.
+    Range(Box<Expr>, Box<Expr>, RangeLimits, Option<Box<Expr>>),
+    List(Vec<Expr>),
+    Tuple(Vec<Expr>),
+    Match(Box<Expr>, Cases),
     Block(Vec<Stmt>),
+    /// `|a, b| body`, an anonymous function.
+    Lambda(Params, Box<Expr>),
+    If(Box<Expr>, Box<Expr>, Option<Box<Expr>>),
+    Interpolation(Vec<StringPart>),
+    /// Stands in for an expression that failed to parse.
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StringPart {
+    Literal(String),
+    Expr(Expr),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatternKind {
+    /// `_`
+    Wildcard,
+    Binding(Symbol),
+    Literal(Value),
+    Range(Value, Value, RangeLimits),
+    Tuple(Vec<Pattern>),
+    List(Vec<Pattern>),
+    /// `..` in a list pattern, matching any number of elements.
+    Rest,
+    /// `Name { field, other: pattern }`
+    Struct(Symbol, Vec<(Symbol, Pattern)>),
+    /// `a | b`
+    Or(Vec<Pattern>),
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum Type {
     String,
     Int,
     Float,
+    Bool,
+    List(Box<Type>),
+    Tuple(Vec<Type>),
+    Func(Vec<Type>, Box<Type>),
 }
 
-pub enum Value {
+#[derive(Clone, Debug, PartialEq)]
+pub struct Value {
+    pub kind: ValueKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueKind {
     Int(i64),
     Float(f64),
     String(String),
-    Ident(String),
+    Bool(bool),
+    Ident(Symbol),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Operation {
     Add,
     Mul,
@@ -47,8 +173,198 @@ pub enum Operation {
     MoreEq,
 }
 
-pub type Param = (String, Option<Type>);
+/// Whether a `let` binding can be assigned to again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mutability {
+    Not,
+    Mut,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RangeLimits {
+    Exclusive,
+    Inclusive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self {
+            kind,
+            span,
+            doc: None,
+        }
+    }
+
+    pub fn with_doc(mut self, doc: Option<String>) -> Self {
+        self.doc = doc;
+        self
+    }
+
+    /// Shifts every span in the statement, see [`Span::shift`].
+    pub fn shift(&mut self, offset: isize, lines: isize) {
+        self.span.shift(offset, lines);
+        match &mut self.kind {
+            StmtKind::Let(_, _, _, expr) | StmtKind::Func(_, _, _, expr) | StmtKind::Expr(expr) => {
+                expr.shift(offset, lines)
+            }
+            StmtKind::Assign(target, _, value) => {
+                target.shift(offset, lines);
+                value.shift(offset, lines);
+            }
+            StmtKind::For(_, expr, body) | StmtKind::While(expr, body) => {
+                expr.shift(offset, lines);
+                body.shift(offset, lines);
+            }
+            StmtKind::Break | StmtKind::Continue | StmtKind::Error => {}
+        }
+    }
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn shift(&mut self, offset: isize, lines: isize) {
+        self.span.shift(offset, lines);
+        match &mut self.kind {
+            ExprKind::Value(value) => value.span.shift(offset, lines),
+            ExprKind::Call(callee, args) => {
+                callee.shift(offset, lines);
+                for arg in args {
+                    arg.shift(offset, lines);
+                }
+            }
+            ExprKind::List(elems) | ExprKind::Tuple(elems) => {
+                for elem in elems {
+                    elem.shift(offset, lines);
+                }
+            }
+            ExprKind::Match(expr, cases) => {
+                expr.shift(offset, lines);
+                for (pattern, guard, body) in cases {
+                    pattern.shift(offset, lines);
+                    if let Some(guard) = guard {
+                        guard.shift(offset, lines);
+                    }
+                    body.shift(offset, lines);
+                }
+            }
+            ExprKind::Range(start, end, _, step) => {
+                start.shift(offset, lines);
+                end.shift(offset, lines);
+                if let Some(step) = step {
+                    step.shift(offset, lines);
+                }
+            }
+            ExprKind::Binary(lhs, _, rhs) => {
+                lhs.shift(offset, lines);
+                rhs.shift(offset, lines);
+            }
+            ExprKind::Unary(_, expr) => expr.shift(offset, lines),
+            ExprKind::Block(stmts) => {
+                for stmt in stmts {
+                    stmt.shift(offset, lines);
+                }
+            }
+            ExprKind::Lambda(_, body) => body.shift(offset, lines),
+            ExprKind::If(cond, then, otherwise) => {
+                cond.shift(offset, lines);
+                then.shift(offset, lines);
+                if let Some(otherwise) = otherwise {
+                    otherwise.shift(offset, lines);
+                }
+            }
+            ExprKind::Interpolation(parts) => {
+                for part in parts {
+                    if let StringPart::Expr(expr) = part {
+                        expr.shift(offset, lines);
+                    }
+                }
+            }
+            ExprKind::Error => {}
+        }
+    }
+}
+
+impl Value {
+    pub fn new(kind: ValueKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl Pattern {
+    pub fn new(kind: PatternKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn shift(&mut self, offset: isize, lines: isize) {
+        self.span.shift(offset, lines);
+        match &mut self.kind {
+            PatternKind::Wildcard | PatternKind::Binding(_) | PatternKind::Rest => {}
+            PatternKind::Literal(value) => value.span.shift(offset, lines),
+            PatternKind::Range(start, end, _) => {
+                start.span.shift(offset, lines);
+                end.span.shift(offset, lines);
+            }
+            PatternKind::Tuple(patterns)
+            | PatternKind::List(patterns)
+            | PatternKind::Or(patterns) => {
+                for pattern in patterns {
+                    pattern.shift(offset, lines);
+                }
+            }
+            PatternKind::Struct(_, fields) => {
+                for (_, pattern) in fields {
+                    pattern.shift(offset, lines);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Operation::Add => "+",
+            Operation::Mul => "*",
+            Operation::Sub => "-",
+            Operation::Div => "/",
+            Operation::EqEq => "==",
+            Operation::Less => "<",
+            Operation::LessEq => "<=",
+            Operation::More => ">",
+            Operation::MoreEq => ">=",
+        };
+        write!(f, "{text}")
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOp::Neg => write!(f, "-"),
+            UnaryOp::Not => write!(f, "!"),
+        }
+    }
+}
+
+impl From<Value> for Expr {
+    fn from(value: Value) -> Self {
+        let span = value.span;
+        Expr::new(ExprKind::Value(value), span)
+    }
+}
+
+pub type Param = (Symbol, Option<Type>);
 pub type Params = Vec<Param>;
 
-pub type Case = (Expr, Expr);
+/// A `match` arm: pattern, optional `if` guard and body.
+pub type Case = (Pattern, Option<Expr>, Expr);
 pub type Cases = Vec<Case>;
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+/// An interned identifier. Equal names share one allocation, so comparing and hashing them only
+/// looks at the pointer. The name is freed with the last symbol for it.
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+/// The names that have symbols, held weakly so the interner doesn't keep them alive.
+#[derive(Default)]
+struct Interner {
+    names: HashMap<Box<str>, Weak<str>>,
+    /// The number of entries at which those of freed names are dropped next.
+    prune_at: usize,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        let mut interner = interner().lock().unwrap();
+        if let Some(symbol) = interner.names.get(name).and_then(Weak::upgrade) {
+            return Symbol(symbol);
+        }
+        if interner.names.len() >= interner.prune_at {
+            interner.names.retain(|_, name| name.strong_count() > 0);
+            interner.prune_at = (interner.names.len() * 2).max(64);
+        }
+        let symbol: Arc<str> = name.into();
+        interner.names.insert(name.into(), Arc::downgrade(&symbol));
+        Symbol(symbol)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).cast::<u8>().hash(state);
+    }
+}
+
+/// Alphabetical, which agrees with equality as equal names are the same symbol.
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::interner;
+    use crate::Symbol;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_intern() {
+        let x = Symbol::intern("x");
+        assert_eq!(x, Symbol::intern("x"));
+        assert_ne!(x, Symbol::intern("y"));
+        assert_eq!(x, "x");
+        assert_eq!(Symbol::from("größe").to_string(), "größe");
+    }
+
+    #[test]
+    fn test_free() {
+        let name = "only_in_test_free";
+        let symbol = Symbol::intern(name);
+        let weak = Arc::downgrade(&symbol.0);
+        assert_eq!(symbol, Symbol::intern(name));
+        drop(symbol);
+        assert_eq!(weak.strong_count(), 0);
+
+        // Interning enough other names drops the entry of the freed one.
+        for i in 0..200 {
+            Symbol::intern(&format!("test_free_{i}"));
+        }
+        assert!(!interner().lock().unwrap().names.contains_key(name));
+    }
+}
@@ -0,0 +1,447 @@
+//! Keeps the tokens and statements of a document up to date as it is edited, redoing only the
+//! work an edit can affect.
+//!
+//! A `Newline` token that isn't inside any brackets or blocks leaves the lexer in the same state
+//! every time. That makes those safe points to resume lexing from, and to stop at once the new
+//! tokens line up with the old ones again.
+
+use crate::lexer::{nest, LexError, Lexer};
+use crate::token::{Token, TokenKind};
+use crate::{ParseError, ParseResult, Parser};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+/// Replaces the text in `range`, a byte range of the current source, with `text`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+/// An edit whose range isn't one of the current source, or splits a character.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvalidEdit(pub Range<usize>);
+
+impl fmt::Display for InvalidEdit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Range { start, end } = self.0;
+        write!(
+            f,
+            "`{start}..{end}` is not a range between characters of the source"
+        )
+    }
+}
+
+impl std::error::Error for InvalidEdit {}
+
+/// What an edit changed. `text` is a byte range of the new source; `tokens` and `stmts` are
+/// indices into the new token and statement lists. Everything outside them was reused, with its
+/// spans moved along.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Changes {
+    pub text: Range<usize>,
+    pub tokens: Range<usize>,
+    pub stmts: Range<usize>,
+}
+
+struct Item {
+    tokens: Range<usize>,
+    stmt: ParseResult,
+    /// Errors recovered from inside the statement, e.g. in one of its blocks.
+    errors: Vec<ParseError>,
+}
+
+pub struct Document {
+    source: String,
+    tokens: Vec<Token<'static>>,
+    lex_errors: Vec<LexError>,
+    items: Vec<Item>,
+}
+
+impl Document {
+    pub fn new(source: impl Into<String>) -> Self {
+        let source = source.into();
+        let (tokens, mut lex_errors) = Lexer::new(&source).lex();
+        lex_errors.sort_by_key(|err| err.span().start);
+        let tokens = tokens.into_iter().map(Token::into_owned).collect();
+        let mut document = Self {
+            source,
+            tokens,
+            lex_errors,
+            items: Vec::new(),
+        };
+        document.items = statements(&document.tokens)
+            .into_iter()
+            .map(|tokens| document.item(tokens))
+            .collect();
+        document
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn tokens(&self) -> &[Token<'static>] {
+        &self.tokens
+    }
+
+    /// Sorted by where they start.
+    pub fn lex_errors(&self) -> &[LexError] {
+        &self.lex_errors
+    }
+
+    /// The top level statements, in order.
+    pub fn stmts(&self) -> impl Iterator<Item = &ParseResult> {
+        self.items.iter().map(|item| &item.stmt)
+    }
+
+    /// The errors the statements were parsed in spite of, in order.
+    pub fn errors(&self) -> impl Iterator<Item = &ParseError> {
+        self.items.iter().flat_map(|item| &item.errors)
+    }
+
+    /// Applies `edit`, unless its range isn't one of the current source or splits a character.
+    /// The document is left as it was then.
+    pub fn edit(&mut self, edit: Edit) -> Result<Changes, InvalidEdit> {
+        let Edit { range, text } = edit;
+        let valid = range.start <= range.end
+            && self.source.is_char_boundary(range.start)
+            && self.source.is_char_boundary(range.end);
+        if !valid {
+            return Err(InvalidEdit(range));
+        }
+        let offset = text.len() as isize - range.len() as isize;
+        let lines = text.matches('\n').count() as isize
+            - self.source[range.clone()].matches('\n').count() as isize;
+        self.source.replace_range(range.clone(), &text);
+        let edit_end = range.start + text.len();
+
+        // Resume after the last top level line break in front of the edit.
+        let top_level = top_level_newlines(&self.tokens);
+        let restart = (0..self.tokens.len())
+            .rev()
+            .find(|&index| top_level[index] && self.tokens[index].span.end <= range.start)
+            .map_or(0, |index| index + 1);
+        let (resume, line) = match restart {
+            0 => (0, 1),
+            restart => {
+                let newline = self.tokens[restart - 1].span;
+                (newline.end, newline.line + 1)
+            }
+        };
+
+        // Lex until a line break after the edit that was also there before it.
+        let mut relexed = Vec::new();
+        let mut errors = Vec::new();
+        let mut sync = None;
+        let mut open = Vec::new();
+        for result in Lexer::resume(&self.source, resume, line) {
+            let token = match result {
+                Ok(token) => token.into_owned(),
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
+            };
+            nest(&mut open, token.kind);
+            let old = token.span.start.checked_add_signed(-offset);
+            let is_sync =
+                token.kind == TokenKind::Newline && open.is_empty() && token.span.start >= edit_end;
+            relexed.push(token);
+            if let Some(old) = old.filter(|_| is_sync) {
+                let found = self.tokens[restart..]
+                    .binary_search_by_key(&old, |token| token.span.start)
+                    .ok()
+                    .filter(|index| top_level[restart + index]);
+                if let Some(index) = found {
+                    sync = Some(restart + index + 1);
+                    break;
+                }
+            }
+        }
+
+        // Splice the new tokens in and move everything after them along.
+        let old_end = sync.unwrap_or(self.tokens.len());
+        let new_end = restart + relexed.len();
+        let mut rest = self.tokens.split_off(old_end);
+        for token in &mut rest {
+            token.span.shift(offset, lines);
+        }
+        self.tokens.truncate(restart);
+        self.tokens.extend(relexed);
+        self.tokens.extend(rest);
+
+        let resumed_at = resume;
+        let synced_at = match sync {
+            Some(_) => self.tokens[new_end - 1].span.end,
+            None => self.source.len(),
+        };
+        let old_synced_at = synced_at.saturating_add_signed(-offset);
+        let mut lex_errors = std::mem::take(&mut self.lex_errors);
+        lex_errors.retain(|err| err.span().end <= resumed_at || err.span().start >= old_synced_at);
+        for err in &mut lex_errors {
+            if err.span().start >= old_synced_at {
+                err.span_mut().shift(offset, lines);
+            }
+        }
+        lex_errors.extend(errors);
+        lex_errors.sort_by_key(|err| err.span().start);
+        self.lex_errors = lex_errors;
+
+        // Reparse the statements that aren't made of exactly the same tokens as before.
+        let moved = new_end as isize - old_end as isize;
+        let mut reusable: HashMap<_, _> = std::mem::take(&mut self.items)
+            .into_iter()
+            .filter_map(|mut item| {
+                let Range { start, end } = item.tokens;
+                if end <= restart {
+                    Some(((start, end), (item.stmt, item.errors)))
+                } else if start >= old_end {
+                    shift(&mut item.stmt, offset, lines);
+                    for err in &mut item.errors {
+                        err.span_mut().shift(offset, lines);
+                    }
+                    let start = start.saturating_add_signed(moved);
+                    let end = end.saturating_add_signed(moved);
+                    Some(((start, end), (item.stmt, item.errors)))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let mut stmts: Option<Range<usize>> = None;
+        for (index, tokens) in statements(&self.tokens).into_iter().enumerate() {
+            let item = match reusable.remove(&(tokens.start, tokens.end)) {
+                Some((stmt, errors)) => Item {
+                    tokens,
+                    stmt,
+                    errors,
+                },
+                None => {
+                    let start = stmts.map_or(index, |stmts| stmts.start);
+                    stmts = Some(start..index + 1);
+                    self.item(tokens)
+                }
+            };
+            self.items.push(item);
+        }
+
+        Ok(Changes {
+            text: resumed_at..synced_at,
+            tokens: restart..new_end,
+            stmts: stmts.unwrap_or_default(),
+        })
+    }
+
+    fn item(&self, tokens: Range<usize>) -> Item {
+        let mut parser = Parser::new(self.tokens[tokens.clone()].to_vec());
+        let stmt = parser.parse_stmt().expect("Statements are never empty.");
+        Item {
+            tokens,
+            stmt,
+            errors: parser.errors,
+        }
+    }
+}
+
+fn shift(stmt: &mut ParseResult, offset: isize, lines: isize) {
+    match stmt {
+        Ok(stmt) => stmt.shift(offset, lines),
+        Err(err) => err.span_mut().shift(offset, lines),
+    }
+}
+
+/// Whether each token is a line break outside of all brackets and blocks.
+fn top_level_newlines(tokens: &[Token]) -> Vec<bool> {
+    let mut open = Vec::new();
+    tokens
+        .iter()
+        .map(|token| {
+            nest(&mut open, token.kind);
+            token.kind == TokenKind::Newline && open.is_empty()
+        })
+        .collect()
+}
+
+/// Splits the tokens into top level statements, each including its doc comments and the `;` or
+/// line break that ends it. Doc comments at the end of the file document nothing and are left out.
+fn statements(tokens: &[Token]) -> Vec<Range<usize>> {
+    let mut statements = Vec::new();
+    let mut start = None;
+    let mut depth = 0usize;
+    for (index, token) in tokens.iter().enumerate() {
+        match token.kind {
+            // The `else` of an `if` may start the next line.
+            TokenKind::Newline
+                if tokens.get(index + 1).map(|token| token.kind) == Some(TokenKind::Else) => {}
+            TokenKind::Newline | TokenKind::Semicolon if depth == 0 => {
+                if let Some(start) = start.take() {
+                    statements.push(start..index + 1);
+                }
+            }
+            kind => {
+                start.get_or_insert(index);
+                match kind {
+                    TokenKind::LeftBrace | TokenKind::LeftBracket | TokenKind::LeftCurlyBracket => {
+                        depth += 1
+                    }
+                    TokenKind::RightBrace
+                    | TokenKind::RightBracket
+                    | TokenKind::RightCurlyBracket => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+    }
+    if let Some(start) = start {
+        let docs = tokens[start..]
+            .iter()
+            .all(|token| token.kind == TokenKind::DocComment);
+        if !docs {
+            statements.push(start..tokens.len());
+        }
+    }
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::incremental::{Changes, Document, Edit, InvalidEdit};
+    use crate::lexer::Lexer;
+    use crate::Parser;
+    use std::ops::Range;
+
+    fn edit(document: &mut Document, from: &str, to: &str) -> Changes {
+        let start = document.source().find(from).unwrap();
+        document
+            .edit(Edit {
+                range: start..start + from.len(),
+                text: to.into(),
+            })
+            .unwrap()
+    }
+
+    /// Compares against lexing and parsing the new source from scratch.
+    fn check(document: &Document) {
+        let (tokens, mut errors) = Lexer::new(document.source()).lex();
+        errors.sort_by_key(|err| err.span().start);
+        assert_eq!(document.tokens(), tokens);
+        assert_eq!(document.lex_errors(), errors);
+        if !errors.is_empty() {
+            return;
+        }
+
+        let mut parser = Parser::new(tokens);
+        let mut stmts = Vec::new();
+        while let Some(stmt) = parser.parse_stmt() {
+            stmts.push(stmt);
+        }
+        assert_eq!(
+            document.stmts().collect::<Vec<_>>(),
+            stmts.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            document.errors().collect::<Vec<_>>(),
+            parser.errors.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_incremental() {
+        let mut document =
+            Document::new("let a = 1\nlet b = a + 2; b\n\n\"{b}\" + \"!\"\nlet c = 3\n");
+        check(&document);
+
+        let changes = edit(&mut document, "2", "20 *\n 4");
+        check(&document);
+        assert_eq!(changes.stmts, 1..3);
+        assert_eq!(changes.tokens, 5..16);
+
+        let changes = edit(&mut document, "1", "100");
+        check(&document);
+        assert_eq!(changes.stmts, 0..1);
+        assert_eq!(changes.text, 0..12);
+
+        let changes = edit(&mut document, "let c", "let d");
+        check(&document);
+        assert_eq!(changes.stmts, 4..5);
+
+        edit(&mut document, "\"{b}\"", "\"{b");
+        check(&document);
+        assert!(!document.lex_errors().is_empty());
+        edit(&mut document, "\"{b", "\"{b}\"");
+        check(&document);
+        assert!(document.lex_errors().is_empty());
+        assert!(document.stmts().all(|stmt| stmt.is_ok()));
+
+        edit(&mut document, "let d = 3", "while 1 { let = 2 }");
+        check(&document);
+        assert_eq!(document.errors().count(), 1);
+        edit(&mut document, "let a", "let  a");
+        check(&document);
+        assert_eq!(document.errors().count(), 1);
+
+        // Line breaks in a block inside brackets are no place to resume from.
+        let mut document = Document::new("let a = f(|x| {\n  let y = x\n  y\n}, 3)\nlet b = 2\n");
+        check(&document);
+        let changes = edit(&mut document, "let y = x", "let y = (x");
+        check(&document);
+        assert_eq!(changes.text.start, 0);
+        edit(&mut document, "let y = (x", "let y = x * 2");
+        check(&document);
+        assert!(document.stmts().all(|stmt| stmt.is_ok()));
+
+        let mut document = Document::new("if a { 1 }\nlet b = 2\n");
+        edit(&mut document, "let b = 2", "else { 2 }");
+        check(&document);
+        assert_eq!(document.stmts().count(), 1);
+
+        let mut document = Document::new("let a = 1\n/// trailing doc");
+        check(&document);
+        assert_eq!(document.stmts().count(), 1);
+        edit(
+            &mut document,
+            "/// trailing doc",
+            "/// trailing doc\nlet b = a",
+        );
+        check(&document);
+        assert_eq!(document.stmts().count(), 2);
+        edit(&mut document, "let b = a", "");
+        check(&document);
+        let end = document.source().len();
+        document
+            .edit(Edit {
+                range: end..end,
+                text: "\n/// x".into(),
+            })
+            .unwrap();
+        check(&document);
+    }
+
+    #[test]
+    fn test_invalid_edits() {
+        let mut document = Document::new("let ä = 1\n");
+        let backwards = Range { start: 3, end: 2 };
+        for range in [5..7, 9..20, 20..21, backwards] {
+            let edit = Edit {
+                range: range.clone(),
+                text: "x".into(),
+            };
+            assert_eq!(document.edit(edit), Err(InvalidEdit(range)));
+        }
+        check(&document);
+        assert_eq!(document.source(), "let ä = 1\n");
+
+        // The errors are in the same order as from scratch, even though the lexer reports the
+        // unterminated string last.
+        let mut document = Document::new("\"{ 1 % 2\nlet b = 2 € 3\n");
+        check(&document);
+        edit(&mut document, "€", "$");
+        check(&document);
+        edit(&mut document, "b", "c @");
+        check(&document);
+        assert_eq!(document.lex_errors().len(), 4);
+    }
+}
@@ -1,252 +1,960 @@
-use crate::token::{Token, TokenKind};
+use crate::number::Number;
+use crate::token::{Operator, Token, TokenKind};
+use ast::{Diagnostic, Span};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fmt;
+use unicode_ident::{is_xid_continue, is_xid_start};
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
 
-#[derive(Clone, Copy, PartialEq)]
-pub enum State {
-    Token(TokenKind),
-    Looking,
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexError {
+    UnknownChar(char, Span),
+    UnterminatedString(Span),
+    UnterminatedComment(Span),
+    InvalidEscape(String, Span),
+    MalformedNumber(String, Span),
 }
 
-pub struct Lexer {
-    buffer: String,
-    input: Vec<char>,
-    state: State,
-    token: Vec<Token>,
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnknownChar(_, span) => *span,
+            LexError::UnterminatedString(span) => *span,
+            LexError::UnterminatedComment(span) => *span,
+            LexError::InvalidEscape(_, span) => *span,
+            LexError::MalformedNumber(_, span) => *span,
+        }
+    }
+
+    /// Whether the input in error is left out of the tokens.
+    pub(crate) fn drops_input(&self) -> bool {
+        matches!(
+            self,
+            LexError::UnknownChar(..)
+                | LexError::UnterminatedString(_)
+                | LexError::UnterminatedComment(_)
+        )
+    }
+
+    pub(crate) fn span_mut(&mut self) -> &mut Span {
+        match self {
+            LexError::UnknownChar(_, span) => span,
+            LexError::UnterminatedString(span) => span,
+            LexError::UnterminatedComment(span) => span,
+            LexError::InvalidEscape(_, span) => span,
+            LexError::MalformedNumber(_, span) => span,
+        }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let span = self.span();
+        match self {
+            LexError::UnknownChar(c, _) => {
+                Diagnostic::error(format!("unknown character `{c}`")).with_label(span, "")
+            }
+            LexError::UnterminatedString(_) => Diagnostic::error("unterminated string")
+                .with_label(span, "the string starts here")
+                .with_help("add a closing `\"`"),
+            LexError::UnterminatedComment(_) => Diagnostic::error("unterminated block comment")
+                .with_label(span, "the comment starts here")
+                .with_help("add a closing `*/` for every `/*`"),
+            LexError::InvalidEscape(escape, _) => {
+                Diagnostic::error(format!("invalid escape `{escape}`"))
+                    .with_label(span, "")
+                    .with_note(
+                        "the escapes are `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"`, `\\'`, \
+                         `\\{`, `\\}` and `\\u{..}`",
+                    )
+            }
+            LexError::MalformedNumber(number, _) => {
+                Diagnostic::error(format!("malformed number `{number}`")).with_label(span, "")
+            }
+        }
+    }
 }
 
-impl Lexer {
-    pub fn new(input: String) -> Self {
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.diagnostic().message)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+pub type LexResult<'a> = Result<Token<'a>, LexError>;
+
+fn unicode_escape(hex: &str) -> Option<char> {
+    if hex.is_empty() || hex.len() > 6 {
+        return None;
+    }
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+#[derive(Clone, Copy)]
+struct Position {
+    offset: usize,
+    line: usize,
+    col: usize,
+}
+
+impl Position {
+    fn advance(&mut self, input: char) {
+        self.offset += input.len_utf8();
+        if input == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+    }
+}
+
+/// Lexes `source` lazily, one token at a time. Token text borrows from `source` unless it had to
+/// be decoded.
+pub struct Lexer<'a> {
+    source: &'a str,
+    position: Position,
+    start: Position,
+    interpolation: Vec<(Position, usize)>,
+    /// The brackets that are open, innermost last.
+    open: Vec<TokenKind>,
+    last: Option<TokenKind>,
+    trivia: bool,
+    pending: VecDeque<LexResult<'a>>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let position = Position {
+            offset: 0,
+            line: 1,
+            col: 1,
+        };
         Self {
-            buffer: String::new(),
-            input: input.chars().collect(),
-            state: State::Looking,
-            token: Vec::new(),
+            source,
+            position,
+            start: position,
+            interpolation: Vec::new(),
+            open: Vec::new(),
+            last: None,
+            trivia: false,
+            pending: VecDeque::new(),
         }
     }
 
-    fn add_token(&mut self, token: Token) {
-        self.token.push(token)
+    /// Also emits whitespace and comments as tokens, so that the tokens cover the whole source.
+    pub fn with_trivia(source: &'a str) -> Self {
+        Self {
+            trivia: true,
+            ..Self::new(source)
+        }
     }
 
-    fn clear_buffer(&mut self) -> String {
-        let buffer = self.buffer.clone();
-        self.buffer.clear();
-        buffer
+    /// Starts lexing at `offset`, which must be the start of line `line` and not inside a string,
+    /// brackets or a statement that continues from the line before.
+    pub fn resume(source: &'a str, offset: usize, line: usize) -> Self {
+        let position = Position {
+            offset,
+            line,
+            col: 1,
+        };
+        Self {
+            position,
+            start: position,
+            ..Self::new(source)
+        }
     }
 
-    fn next_state(&mut self, input: char) {
-        match (&self.state, input) {
-            (State::Looking, ',') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::Comma, buffer));
-            }
-            (State::Looking, ':') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::Colon, buffer));
-            }
-            (State::Looking, '+') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::Add, buffer));
-            }
-            (State::Looking, '*') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::Mul, buffer));
-            }
-            (State::Looking, '/') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::Div, buffer));
-            }
-            (State::Looking, '[') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::LeftBracket, buffer));
-            }
-            (State::Looking, ']') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::RightBracket, buffer));
-            }
-            (State::Looking, '{') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::LeftCurlyBracket, buffer));
-            }
-            (State::Looking, '}') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::RightCurlyBracket, buffer));
-            }
-            (State::Looking, '(') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::LeftBrace, buffer));
-            }
-            (State::Looking, ')') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::RightBrace, buffer));
-            }
-            (State::Looking, '=') => {
-                self.buffer.push(input);
-                self.state = State::Token(TokenKind::EqEq);
-            }
-            (State::Token(TokenKind::EqEq), '=') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::EqEq, buffer));
-                self.state = State::Looking;
-            }
-            (State::Token(TokenKind::EqEq), input) => {
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::Eq, buffer));
-                self.state = State::Looking;
-                self.next_state(input);
-            }
-            (State::Looking, '>') => {
-                self.buffer.push(input);
-                self.state = State::Token(TokenKind::MoreEq);
+    pub fn lex(self) -> (Vec<Token<'a>>, Vec<LexError>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for result in self {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(err) => errors.push(err),
             }
-            (State::Token(TokenKind::MoreEq), '=') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::MoreEq, buffer));
-                self.state = State::Looking;
-            }
-            (State::Token(TokenKind::MoreEq), input) => {
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::More, buffer));
-                self.state = State::Looking;
-                self.next_state(input);
-            }
-            (State::Looking, '<') => {
-                self.buffer.push(input);
-                self.state = State::Token(TokenKind::Less);
-            }
-            (State::Token(TokenKind::LessEq), '=') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::LessEq, buffer));
-                self.state = State::Looking;
-            }
-            (State::Token(TokenKind::Less), input) => {
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::Less, buffer));
-                self.state = State::Looking;
-                self.next_state(input);
-            }
-            (State::Looking, '"') => {
-                self.buffer.push(input);
-                self.state = State::Token(TokenKind::String);
-            }
-            (State::Token(TokenKind::String), '\"') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::String, buffer));
-                self.state = State::Looking;
-            }
-            (State::Token(TokenKind::String), input) => {
-                self.buffer.push(input);
-                self.state = State::Token(TokenKind::String);
-            }
-            (State::Looking, '-') => {
-                self.buffer.push(input);
-                self.state = State::Token(TokenKind::Sub);
-            }
-            (State::Token(TokenKind::Sub), '>') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::RightArrow, buffer));
-                self.state = State::Looking;
-            }
-            (State::Token(TokenKind::Sub), '<') => {
-                self.buffer.push(input);
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::LeftArrow, buffer));
-                self.state = State::Looking;
-            }
-            (State::Token(TokenKind::Sub), input) => {
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::Sub, buffer));
-                self.state = State::Looking;
-                self.next_state(input);
-            }
-            (State::Looking, '0'..='9') => {
-                self.buffer.push(input);
-                self.state = State::Token(TokenKind::Int);
-            }
-            (State::Token(TokenKind::Int), '0'..='9') => {
-                self.buffer.push(input);
-            }
-            (State::Token(TokenKind::Float), '0'..='9') => {
-                self.buffer.push(input);
-            }
-            (State::Token(TokenKind::Int), '.') => {
-                self.buffer.push(input);
-                self.state = State::Token(TokenKind::Float);
-            }
-            (State::Token(TokenKind::Int), _) => {
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::Int, buffer));
-                self.state = State::Looking;
-                self.next_state(input);
-            }
-            (State::Token(TokenKind::Float), _) => {
-                let buffer = self.clear_buffer();
-                self.add_token(Token::new(TokenKind::Float, buffer));
-                self.state = State::Looking;
-                self.next_state(input);
-            }
-            (State::Looking, 'a'..='z' | 'A'..='Z') => {
-                self.buffer.push(input);
-                self.state = State::Token(TokenKind::Ident);
-            }
-            (State::Token(TokenKind::Ident), 'a'..='z' | 'A'..='Z' | '0'..='9') => {
-                self.buffer.push(input);
-            }
-            (State::Token(TokenKind::Ident), _) => {
-                let buffer = self.clear_buffer();
-                self.add_token(Token::kw_or_ident(buffer));
-                self.state = State::Looking;
-                self.next_state(input);
+        }
+        (tokens, errors)
+    }
+
+    pub fn peek(&mut self) -> Option<&LexResult<'a>> {
+        self.peek_nth(0)
+    }
+
+    /// Looks `n` tokens ahead without consuming anything.
+    pub fn peek_nth(&mut self, n: usize) -> Option<&LexResult<'a>> {
+        while self.pending.len() <= n && self.scan() {}
+        self.pending.get(n)
+    }
+
+    /// Span covering everything from the start of the current token up to the cursor.
+    fn span(&self) -> Span {
+        self.span_from(self.start)
+    }
+
+    fn span_from(&self, start: Position) -> Span {
+        Span::new(start.offset, self.position.offset, start.line, start.col)
+    }
+
+    fn text(&self) -> &'a str {
+        &self.source[self.start.offset..self.position.offset]
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.source[self.position.offset..].chars().next()
+    }
+
+    fn peek_char_nth(&self, n: usize) -> Option<char> {
+        self.source[self.position.offset..].chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let input = self.peek_char()?;
+        self.position.advance(input);
+        Some(input)
+    }
+
+    fn eat(&mut self, input: char) -> bool {
+        let eaten = self.peek_char() == Some(input);
+        if eaten {
+            self.bump();
+        }
+        eaten
+    }
+
+    fn eat_while(&mut self, predicate: impl Fn(char) -> bool) {
+        while self.peek_char().is_some_and(&predicate) {
+            self.bump();
+        }
+    }
+
+    fn emit(&mut self, kind: TokenKind) {
+        let token = Token::new(kind, self.text().into(), self.span());
+        self.push(token);
+    }
+
+    fn push(&mut self, token: Token<'a>) {
+        if !token.kind.is_trivia() {
+            self.last = Some(token.kind);
+        }
+        nest(&mut self.open, token.kind);
+        self.pending.push_back(Ok(token));
+    }
+
+    fn emit_trivia(&mut self, kind: TokenKind) {
+        if self.trivia {
+            self.emit(kind);
+        }
+    }
+
+    /// A line break ends the statement unless we are directly inside parentheses or brackets, or in
+    /// an interpolated expression, or the line ends in something that needs a right hand side. A
+    /// block opens a new line of statements, even when it is itself inside brackets.
+    fn newline(&mut self) {
+        let continues = match self.last {
+            Some(kind) => matches!(
+                kind,
+                TokenKind::Op(_)
+                    | TokenKind::AssignOp(_)
+                    | TokenKind::Eq
+                    | TokenKind::Comma
+                    | TokenKind::Colon
+                    | TokenKind::DotDot
+                    | TokenKind::DotDotEq
+                    | TokenKind::RightArrow
+                    | TokenKind::LeftArrow
+                    | TokenKind::FatArrow
+                    | TokenKind::Pipe
+                    | TokenKind::LeftBrace
+                    | TokenKind::LeftBracket
+                    | TokenKind::LeftCurlyBracket
+                    | TokenKind::Let
+                    | TokenKind::Mut
+                    | TokenKind::If
+                    | TokenKind::Else
+                    | TokenKind::For
+                    | TokenKind::While
+                    | TokenKind::Match
+                    | TokenKind::Newline
+                    | TokenKind::Semicolon
+                    | TokenKind::DocComment
+            ),
+            None => true,
+        };
+        let in_block = matches!(self.open.last(), None | Some(TokenKind::LeftCurlyBracket));
+        if !continues && in_block && self.interpolation.is_empty() {
+            self.emit(TokenKind::Newline);
+        } else {
+            self.emit_trivia(TokenKind::Whitespace);
+        }
+    }
+
+    fn error(&mut self, err: LexError) {
+        self.pending.push_back(Err(err));
+    }
+
+    /// Lexes the next lexeme into `pending`. Unless we keep trivia, whitespace and plain comments
+    /// produce nothing.
+    /// Returns `false` once the input is exhausted.
+    fn scan(&mut self) -> bool {
+        self.start = self.position;
+        let Some(input) = self.bump() else {
+            // Strings inside an interpolated expression that end here are part of the outermost
+            // string, which is reported for all of them.
+            let unterminated = std::mem::take(&mut self.interpolation);
+            if let Some((start, _)) = unterminated.first() {
+                let span = self.span_from(*start);
+                self.error(LexError::UnterminatedString(span));
+            }
+            return !unterminated.is_empty();
+        };
+
+        match input {
+            ' ' | '\t' | '\r' => {
+                self.eat_while(|c| matches!(c, ' ' | '\t' | '\r'));
+                self.emit_trivia(TokenKind::Whitespace);
+            }
+            '\n' => self.newline(),
+            ';' => self.emit(TokenKind::Semicolon),
+            ',' => self.emit(TokenKind::Comma),
+            ':' => self.emit(TokenKind::Colon),
+            '.' if self.eat('.') => match self.eat('=') {
+                true => self.emit(TokenKind::DotDotEq),
+                false => self.emit(TokenKind::DotDot),
+            },
+            '+' if self.eat('=') => self.emit(TokenKind::AssignOp(Operator::Add)),
+            '+' => self.emit(TokenKind::Op(Operator::Add)),
+            '*' if self.eat('=') => self.emit(TokenKind::AssignOp(Operator::Mul)),
+            '*' => self.emit(TokenKind::Op(Operator::Mul)),
+            '!' => self.emit(TokenKind::Op(Operator::Not)),
+            '|' => self.emit(TokenKind::Pipe),
+            '[' => self.emit(TokenKind::LeftBracket),
+            '(' => self.emit(TokenKind::LeftBrace),
+            ']' => self.emit(TokenKind::RightBracket),
+            ')' => self.emit(TokenKind::RightBrace),
+            '{' => {
+                if let Some((_, depth)) = self.interpolation.last_mut() {
+                    *depth += 1;
+                }
+                self.emit(TokenKind::LeftCurlyBracket);
+            }
+            '}' => match self.interpolation.last_mut() {
+                Some((_, 0)) => {
+                    self.interpolation.pop();
+                    self.string(true);
+                }
+                Some((_, depth)) => {
+                    *depth -= 1;
+                    self.emit(TokenKind::RightCurlyBracket);
+                }
+                None => self.emit(TokenKind::RightCurlyBracket),
+            },
+            '=' if self.eat('=') => self.emit(TokenKind::Op(Operator::EqEq)),
+            '=' if self.eat('>') => self.emit(TokenKind::FatArrow),
+            '=' => self.emit(TokenKind::Eq),
+            '>' if self.eat('=') => self.emit(TokenKind::Op(Operator::MoreEq)),
+            '>' => self.emit(TokenKind::Op(Operator::More)),
+            '<' if self.eat('=') => self.emit(TokenKind::Op(Operator::LessEq)),
+            '<' => self.emit(TokenKind::Op(Operator::Less)),
+            '-' if self.eat('>') => self.emit(TokenKind::RightArrow),
+            '-' if self.eat('<') => self.emit(TokenKind::LeftArrow),
+            '-' if self.eat('=') => self.emit(TokenKind::AssignOp(Operator::Sub)),
+            '-' => self.emit(TokenKind::Op(Operator::Sub)),
+            '/' if self.eat('/') => self.line_comment(),
+            '/' if self.eat('*') => self.block_comment(),
+            '/' if self.eat('=') => self.emit(TokenKind::AssignOp(Operator::Div)),
+            '/' => self.emit(TokenKind::Op(Operator::Div)),
+            '"' => self.string(false),
+            'r' if self.eat('"') => self.raw_string(),
+            '0'..='9' => self.number(),
+            input if input == '_' || is_xid_start(input) => self.ident(),
+            input => self.error(LexError::UnknownChar(input, self.span())),
+        }
+        true
+    }
+
+    /// Identifiers are normalized to NFC so that differently composed spellings of the same
+    /// name compare equal.
+    fn ident(&mut self) {
+        self.eat_while(is_xid_continue);
+        let text = self.text();
+        let value = match is_nfc_quick(text.chars()) {
+            IsNormalized::Yes => Cow::Borrowed(text),
+            _ => Cow::Owned(text.nfc().collect()),
+        };
+        self.push(Token::kw_or_ident(value, self.span()));
+    }
+
+    fn number(&mut self) {
+        loop {
+            match (self.peek_char(), self.peek_char_nth(1)) {
+                (Some('a'..='z' | 'A'..='Z' | '0'..='9' | '_'), _) => {}
+                (Some('.'), Some('.')) => break,
+                (Some('.'), _) => {}
+                (Some('+' | '-'), _)
+                    if self.text().ends_with(['e', 'E'])
+                        && !matches!(self.text().get(..2), Some("0x" | "0X")) => {}
+                _ => break,
+            }
+            self.bump();
+        }
+
+        match Number::parse(self.text()) {
+            Some(Number { float: true, .. }) => self.emit(TokenKind::Float),
+            Some(_) => self.emit(TokenKind::Int),
+            None => {
+                self.error(LexError::MalformedNumber(self.text().into(), self.span()));
+                // Still a number to the parser, so the error isn't followed by others about the
+                // missing value.
+                let kind = if self.text().contains('.') {
+                    TokenKind::Float
+                } else {
+                    TokenKind::Int
+                };
+                self.emit(kind);
             }
-            (State::Looking, ' ' | '\n' | '\t') => {}
-            _ => unreachable!("Should not happen."),
         }
     }
 
-    pub fn lex(mut self) -> Vec<Token> {
-        while let Some(_) = self.input.get(0) {
-            let c = self.input.remove(0);
-            self.next_state(c);
+    /// Lexes a string literal (or the rest of one after an interpolated expression). The
+    /// contents are only copied once an escape sequence forces them to be decoded.
+    fn string(&mut self, continued: bool) {
+        let contents = self.position.offset;
+        let mut decoded: Option<String> = None;
+        loop {
+            let end = self.position.offset;
+            let kind = match self.peek_char() {
+                None => {
+                    self.unterminated_string();
+                    return;
+                }
+                Some('"') if continued => TokenKind::InterpEnd,
+                Some('"') => TokenKind::String,
+                Some('{') if continued => TokenKind::InterpMid,
+                Some('{') => TokenKind::InterpStart,
+                Some('\\') => {
+                    let decoded = decoded.get_or_insert_with(|| self.source[contents..end].into());
+                    if let Some(input) = self.escape() {
+                        decoded.push(input);
+                    }
+                    continue;
+                }
+                Some(input) => {
+                    self.bump();
+                    if let Some(decoded) = decoded.as_mut() {
+                        decoded.push(input);
+                    }
+                    continue;
+                }
+            };
+
+            self.bump();
+            let value = match decoded {
+                Some(decoded) => Cow::Owned(decoded),
+                None => Cow::Borrowed(&self.source[contents..end]),
+            };
+            self.push(Token::new(kind, value, self.span()));
+            if let TokenKind::InterpStart | TokenKind::InterpMid = kind {
+                self.interpolation.push((self.start, 0));
+            }
+            return;
         }
-        if let State::Token(kind) = self.state {
-            self.token.push(Token::new(kind, self.buffer))
+    }
+
+    /// Decodes the escape sequence at the cursor, reporting it if it's invalid.
+    fn escape(&mut self) -> Option<char> {
+        let start = self.position;
+        self.bump();
+        let decoded = match self.peek_char()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            input @ ('\\' | '"' | '\'' | '{' | '}') => input,
+            'u' => {
+                self.bump();
+                let decoded = match self.eat('{') {
+                    true => {
+                        let hex = self.position.offset;
+                        self.eat_while(|c| c.is_ascii_hexdigit());
+                        let hex = &self.source[hex..self.position.offset];
+                        match self.eat('}') {
+                            true => unicode_escape(hex),
+                            false => None,
+                        }
+                    }
+                    false => None,
+                };
+                if decoded.is_none() {
+                    let span = self.span_from(start);
+                    let escape = self.source[start.offset..self.position.offset].into();
+                    self.error(LexError::InvalidEscape(escape, span));
+                }
+                return decoded;
+            }
+            _ => {
+                self.bump();
+                let span = self.span_from(start);
+                let escape = self.source[start.offset..self.position.offset].into();
+                self.error(LexError::InvalidEscape(escape, span));
+                return None;
+            }
+        };
+        self.bump();
+        Some(decoded)
+    }
+
+    /// Reports a string that runs to the end of the input, unless it is in an interpolated
+    /// expression: then the end of the input reports the string around it.
+    fn unterminated_string(&mut self) {
+        if self.interpolation.is_empty() {
+            self.error(LexError::UnterminatedString(self.span()));
+        }
+    }
+
+    fn raw_string(&mut self) {
+        let contents = self.position.offset;
+        self.eat_while(|c| c != '"');
+        let end = self.position.offset;
+        if !self.eat('"') {
+            self.unterminated_string();
+            return;
+        }
+        let value = Cow::Borrowed(&self.source[contents..end]);
+        self.push(Token::new(TokenKind::String, value, self.span()));
+    }
+
+    fn line_comment(&mut self) {
+        self.eat_while(|c| c != '\n');
+        let text = self.text();
+        if text.starts_with("///") && !text.starts_with("////") {
+            let doc = text[3..].strip_prefix(' ').unwrap_or(&text[3..]);
+            let doc = doc.trim_end_matches('\r');
+            self.push(Token::new(TokenKind::DocComment, doc.into(), self.span()));
+        } else {
+            self.emit_trivia(TokenKind::Comment);
+        }
+    }
+
+    fn block_comment(&mut self) {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.bump() {
+                Some('/') if self.eat('*') => depth += 1,
+                Some('*') if self.eat('/') => depth -= 1,
+                Some(_) => {}
+                None => {
+                    self.error(LexError::UnterminatedComment(self.span()));
+                    return;
+                }
+            }
         }
-        self.token
+        self.emit_trivia(TokenKind::Comment);
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = LexResult<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && self.scan() {}
+        self.pending.pop_front()
+    }
+}
+
+/// Keeps track of the open brackets as tokens go past. A closing bracket closes the innermost one
+/// of its kind along with anything left open inside it, and is ignored if none is open.
+pub(crate) fn nest(open: &mut Vec<TokenKind>, kind: TokenKind) {
+    let opening = match kind {
+        TokenKind::LeftBrace | TokenKind::LeftBracket | TokenKind::LeftCurlyBracket => {
+            return open.push(kind)
+        }
+        TokenKind::RightBrace => TokenKind::LeftBrace,
+        TokenKind::RightBracket => TokenKind::LeftBracket,
+        TokenKind::RightCurlyBracket => TokenKind::LeftCurlyBracket,
+        _ => return,
+    };
+    if let Some(index) = open.iter().rposition(|kind| *kind == opening) {
+        open.truncate(index);
     }
 }
 
 #[cfg(test)]
 pub mod tests {
     use crate::{
-        lexer::Lexer,
-        token::{Token, TokenKind},
+        lexer::{LexError, Lexer},
+        token::{Operator, Token, TokenKind},
+        ParseError, Parser,
     };
+    use ast::Span;
+    use std::borrow::Cow;
 
     #[test]
     pub fn test_lexer() {
         let input = "let x = 10 =  : ";
-        let lexer = Lexer::new(input.into());
-        let tokens = lexer.lex();
-        assert_eq!(tokens[0], Token::new(TokenKind::Let, "let".into()));
-        assert_eq!(tokens[1], Token::new(TokenKind::Ident, "x".into()));
-        assert_eq!(tokens[2], Token::new(TokenKind::Eq, "=".into()));
-        assert_eq!(tokens[3], Token::new(TokenKind::Int, "10".into()));
-        assert_eq!(tokens[4], Token::new(TokenKind::Eq, "=".into()));
-        assert_eq!(tokens[5], Token::new(TokenKind::Colon, ":".into()));
+        let lexer = Lexer::new(input);
+        let (tokens, errors) = lexer.lex();
+        assert!(errors.is_empty());
+        assert_eq!(
+            tokens[0],
+            Token::new(TokenKind::Let, "let".into(), Span::new(0, 3, 1, 1))
+        );
+        assert_eq!(
+            tokens[1],
+            Token::new(TokenKind::Ident, "x".into(), Span::new(4, 5, 1, 5))
+        );
+        assert_eq!(
+            tokens[2],
+            Token::new(TokenKind::Eq, "=".into(), Span::new(6, 7, 1, 7))
+        );
+        assert_eq!(
+            tokens[3],
+            Token::new(TokenKind::Int, "10".into(), Span::new(8, 10, 1, 9))
+        );
+        assert_eq!(
+            tokens[4],
+            Token::new(TokenKind::Eq, "=".into(), Span::new(11, 12, 1, 12))
+        );
+        assert_eq!(
+            tokens[5],
+            Token::new(TokenKind::Colon, ":".into(), Span::new(14, 15, 1, 15))
+        );
+    }
+
+    #[test]
+    pub fn test_lexer_spans() {
+        let input = "let add(a, b) =\n  a >= b";
+        let (tokens, _) = Lexer::new(input).lex();
+        let ge = &tokens[9];
+        assert_eq!(ge.kind, TokenKind::Op(Operator::MoreEq));
+        assert_eq!(ge.span, Span::new(20, 22, 2, 5));
+        assert_eq!(&input[ge.span.start..ge.span.end], ">=");
+        let last = tokens.last().unwrap();
+        assert_eq!(last.value, "b");
+        assert_eq!(last.span, Span::new(23, 24, 2, 8));
+    }
+
+    #[test]
+    pub fn test_lexer_errors() {
+        let input = "let x = 1 % 2; let y = 12ab\nlet z = \"abc";
+        let (tokens, errors) = Lexer::new(input).lex();
+        assert_eq!(
+            errors,
+            vec![
+                LexError::UnknownChar('%', Span::new(10, 11, 1, 11)),
+                LexError::MalformedNumber("12ab".into(), Span::new(23, 27, 1, 24)),
+                LexError::UnterminatedString(Span::new(36, 40, 2, 9)),
+            ]
+        );
+        let kinds: Vec<_> = tokens.iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Let,
+                TokenKind::Ident,
+                TokenKind::Eq,
+                TokenKind::Int,
+                TokenKind::Int,
+                TokenKind::Semicolon,
+                TokenKind::Let,
+                TokenKind::Ident,
+                TokenKind::Eq,
+                TokenKind::Int,
+                TokenKind::Newline,
+                TokenKind::Let,
+                TokenKind::Ident,
+                TokenKind::Eq,
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_malformed_number() {
+        for input in ["let x = 1e5i32", "let x = 1.5.2\nx", "let x = -12ab"] {
+            let (_, errors) = Parser::from_lexer(Lexer::new(input)).parse_partial();
+            assert!(
+                matches!(errors[..], [ParseError::Lex(LexError::MalformedNumber(..))]),
+                "{input}: {errors:?}"
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_lexer_comments() {
+        let input = "a / b // c\n/* d /* e */ f */ g\n/// Docs.\n//// h\nlet";
+        let (tokens, errors) = Lexer::new(input).lex();
+        assert!(errors.is_empty());
+        let kinds: Vec<_> = tokens.iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Ident,
+                TokenKind::Op(Operator::Div),
+                TokenKind::Ident,
+                TokenKind::Newline,
+                TokenKind::Ident,
+                TokenKind::Newline,
+                TokenKind::DocComment,
+                TokenKind::Let,
+            ]
+        );
+        assert_eq!(tokens[4].value, "g");
+        assert_eq!(tokens[6].value, "Docs.");
+
+        let (_, errors) = Lexer::new("/* a /* b */").lex();
+        assert_eq!(
+            errors,
+            vec![LexError::UnterminatedComment(Span::new(0, 12, 1, 1))]
+        );
+    }
+
+    #[test]
+    pub fn test_lexer_strings() {
+        let input = "\"a\\n\\t\\\\\\\"\\u{1F600}\" r\"C:\\path\" \"two\nlines\"";
+        let (tokens, errors) = Lexer::new(input).lex();
+        assert!(errors.is_empty());
+        assert_eq!(tokens[0].value, "a\n\t\\\"\u{1F600}");
+        assert_eq!(tokens[0].span, Span::new(0, 20, 1, 1));
+        assert_eq!(tokens[1].value, "C:\\path");
+        assert_eq!(tokens[1].span, Span::new(21, 31, 1, 22));
+        assert_eq!(tokens[2].value, "two\nlines");
+
+        let (tokens, errors) = Lexer::new("\"a\\qb\\u{110000}\\u12\"").lex();
+        assert_eq!(tokens[0].value, "ab12");
+        assert_eq!(
+            errors,
+            vec![
+                LexError::InvalidEscape("\\q".into(), Span::new(2, 4, 1, 3)),
+                LexError::InvalidEscape("\\u{110000}".into(), Span::new(5, 15, 1, 6)),
+                LexError::InvalidEscape("\\u".into(), Span::new(15, 17, 1, 16)),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_lexer_interpolation() {
+        let input = "\"hi {name}, {f({a: \"{b}\"})} \\{x\\}\"";
+        let (tokens, errors) = Lexer::new(input).lex();
+        assert!(errors.is_empty());
+        let parts: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.kind, token.value.as_ref()))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                (TokenKind::InterpStart, "hi "),
+                (TokenKind::Ident, "name"),
+                (TokenKind::InterpMid, ", "),
+                (TokenKind::Ident, "f"),
+                (TokenKind::LeftBrace, "("),
+                (TokenKind::LeftCurlyBracket, "{"),
+                (TokenKind::Ident, "a"),
+                (TokenKind::Colon, ":"),
+                (TokenKind::InterpStart, ""),
+                (TokenKind::Ident, "b"),
+                (TokenKind::InterpEnd, ""),
+                (TokenKind::RightCurlyBracket, "}"),
+                (TokenKind::RightBrace, ")"),
+                (TokenKind::InterpEnd, " {x}"),
+            ]
+        );
+        assert_eq!(tokens[0].span, Span::new(0, 5, 1, 1));
+        assert_eq!(tokens[2].span, Span::new(9, 13, 1, 10));
+
+        let (_, errors) = Lexer::new("\"a {b").lex();
+        assert_eq!(
+            errors,
+            vec![LexError::UnterminatedString(Span::new(0, 5, 1, 1))]
+        );
+        // Reported once, for the outermost string.
+        for input in ["\"{\"", "\"a {\"{b"] {
+            let (_, errors) = Lexer::new(input).lex();
+            let span = Span::new(0, input.len(), 1, 1);
+            assert_eq!(errors, vec![LexError::UnterminatedString(span)], "{input}");
+        }
+    }
+
+    #[test]
+    pub fn test_lexer_iterator() {
+        let input = "let x = \"plain\" % \"esc\\n\"";
+        let mut lexer = Lexer::new(input);
+        assert_eq!(
+            lexer.peek_nth(2).unwrap().as_ref().unwrap().kind,
+            TokenKind::Eq
+        );
+        assert_eq!(lexer.peek().unwrap().as_ref().unwrap().kind, TokenKind::Let);
+
+        let results: Vec<_> = lexer.collect();
+        assert_eq!(results.len(), 6);
+        let Ok(plain) = &results[3] else {
+            panic!("expected a string");
+        };
+        assert!(matches!(plain.value, Cow::Borrowed("plain")));
+        assert_eq!(
+            results[4],
+            Err(LexError::UnknownChar('%', Span::new(16, 17, 1, 17)))
+        );
+        let Ok(escaped) = &results[5] else {
+            panic!("expected a string");
+        };
+        assert!(matches!(&escaped.value, Cow::Owned(value) if value == "esc\n"));
+    }
+
+    #[test]
+    pub fn test_lexer_unicode_idents() {
+        let input = "let my_var _unused café λ2 cafe\u{301} x·y";
+        let (tokens, errors) = Lexer::new(input).lex();
+        assert!(errors.is_empty());
+        let idents: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.kind, token.value.as_ref()))
+            .collect();
+        assert_eq!(
+            idents,
+            vec![
+                (TokenKind::Let, "let"),
+                (TokenKind::Ident, "my_var"),
+                (TokenKind::Ident, "_unused"),
+                (TokenKind::Ident, "café"),
+                (TokenKind::Ident, "λ2"),
+                (TokenKind::Ident, "café"),
+                (TokenKind::Ident, "x·y"),
+            ]
+        );
+        assert_eq!(tokens[5].span, Span::new(29, 35, 1, 28));
+        assert_eq!(tokens[3].symbol, tokens[5].symbol);
+        assert_eq!(tokens[0].symbol, None);
+
+        let (_, errors) = Lexer::new("a € b").lex();
+        assert_eq!(
+            errors,
+            vec![LexError::UnknownChar('€', Span::new(2, 5, 1, 3))]
+        );
+    }
+
+    #[test]
+    pub fn test_lexer_newlines() {
+        let input = "a\n\nb +\nc\nf(\nd,\ne\n)\n\"{\nx\n}\"\n";
+        let (tokens, errors) = Lexer::new(input).lex();
+        assert!(errors.is_empty());
+        let kinds: Vec<_> = tokens.iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Ident,
+                TokenKind::Newline,
+                TokenKind::Ident,
+                TokenKind::Op(Operator::Add),
+                TokenKind::Ident,
+                TokenKind::Newline,
+                TokenKind::Ident,
+                TokenKind::LeftBrace,
+                TokenKind::Ident,
+                TokenKind::Comma,
+                TokenKind::Ident,
+                TokenKind::RightBrace,
+                TokenKind::Newline,
+                TokenKind::InterpStart,
+                TokenKind::Ident,
+                TokenKind::InterpEnd,
+                TokenKind::Newline,
+            ]
+        );
+        assert_eq!(tokens[1].span, Span::new(1, 2, 1, 2));
+
+        // A block inside brackets still separates its statements by lines.
+        let (tokens, _) = Lexer::new("[{\n1\n2\n}, (\n3)]").lex();
+        let kinds: Vec<_> = tokens.iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::LeftBracket,
+                TokenKind::LeftCurlyBracket,
+                TokenKind::Int,
+                TokenKind::Newline,
+                TokenKind::Int,
+                TokenKind::Newline,
+                TokenKind::RightCurlyBracket,
+                TokenKind::Comma,
+                TokenKind::LeftBrace,
+                TokenKind::Int,
+                TokenKind::RightBrace,
+                TokenKind::RightBracket,
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_lexer_ranges() {
+        let (tokens, errors) = Lexer::new("0..10 1.5..=n ..\n2").lex();
+        assert!(errors.is_empty());
+        let kinds: Vec<_> = tokens.iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Int,
+                TokenKind::DotDot,
+                TokenKind::Int,
+                TokenKind::Float,
+                TokenKind::DotDotEq,
+                TokenKind::Ident,
+                TokenKind::DotDot,
+                TokenKind::Int,
+            ]
+        );
+        assert_eq!(tokens[4].span, Span::new(9, 12, 1, 10));
+    }
+
+    #[test]
+    pub fn test_lexer_assignment() {
+        let (tokens, errors) = Lexer::new("let mut x = 1\nx += 2 -=\n3 x*=4 /= - =").lex();
+        assert!(errors.is_empty());
+        let kinds: Vec<_> = tokens.iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Let,
+                TokenKind::Mut,
+                TokenKind::Ident,
+                TokenKind::Eq,
+                TokenKind::Int,
+                TokenKind::Newline,
+                TokenKind::Ident,
+                TokenKind::AssignOp(Operator::Add),
+                TokenKind::Int,
+                TokenKind::AssignOp(Operator::Sub),
+                TokenKind::Int,
+                TokenKind::Ident,
+                TokenKind::AssignOp(Operator::Mul),
+                TokenKind::Int,
+                TokenKind::AssignOp(Operator::Div),
+                TokenKind::Op(Operator::Sub),
+                TokenKind::Eq,
+            ]
+        );
+        assert_eq!(tokens[7].span, Span::new(16, 18, 2, 3));
+    }
+
+    #[test]
+    pub fn test_lexer_trivia() {
+        let input = "let x = 1 // one\n\n/* two */ x +\n  x\n";
+        let tokens: Vec<_> = Lexer::with_trivia(input).map(Result::unwrap).collect();
+        let text: String = tokens
+            .iter()
+            .map(|token| &input[token.span.start..token.span.end])
+            .collect();
+        assert_eq!(text, input);
+        let kinds: Vec<_> = tokens
+            .iter()
+            .filter(|token| !token.kind.is_trivia())
+            .map(|token| token.kind)
+            .collect();
+        let (plain, _) = Lexer::new(input).lex();
+        assert_eq!(
+            kinds,
+            plain.iter().map(|token| token.kind).collect::<Vec<_>>()
+        );
     }
 }
@@ -1,19 +1,156 @@
-use ast::Value;
-use std::collections::LinkedList;
-use token::{Token, TokenKind};
+use ast::{Diagnostic, Span, Value, ValueKind};
+use lexer::{LexError, Lexer};
+use number::Number;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io};
+use syntax::SyntaxKind;
+use token::{Operator, Token, TokenKind};
 
+pub mod incremental;
 pub mod lexer;
+pub mod number;
+pub mod syntax;
 pub mod token;
 
-pub struct Parser {
+pub struct Parser<'a> {
     position: usize,
-    tokens: Vec<Token>,
+    tokens: Vec<Token<'a>>,
+    lexer: Option<Lexer<'a>>,
+    lex_errors: Vec<LexError>,
+    /// Errors the parser recovered from, see [`Parser::recover`].
+    errors: Vec<ParseError>,
+    /// The syntax nodes parsed so far and the tokens they cover, see [`syntax::parse`].
+    nodes: Vec<(SyntaxKind, Range<usize>)>,
+    name: Option<String>,
+    path: Option<PathBuf>,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum ParseError {
-    Expected(String, TokenKind),
-    UnExpected(String, Token),
-    UnExpectedEof,
+    Expected(String, TokenKind, Span),
+    UnExpected(String, Token<'static>),
+    UnExpectedEof(Span),
+    OutOfRange(String, Span),
+    /// The input ended before the delimiter opened at the span was closed.
+    Unclosed(TokenKind, Span),
+    Lex(LexError),
+}
+
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::Expected(_, _, span) => *span,
+            ParseError::UnExpected(_, token) => token.span,
+            ParseError::UnExpectedEof(span) => *span,
+            ParseError::OutOfRange(_, span) => *span,
+            ParseError::Unclosed(_, span) => *span,
+            ParseError::Lex(err) => err.span(),
+        }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let span = self.span();
+        match self {
+            ParseError::Expected(context, kind, _) => {
+                Diagnostic::error(format!("expected {kind} in {}", describe(context)))
+                    .with_label(span, format!("expected {kind}"))
+            }
+            ParseError::UnExpected(context, token) => {
+                let found = token.describe();
+                let diagnostic =
+                    Diagnostic::error(format!("unexpected {found} in {}", describe(context)))
+                        .with_label(span, format!("unexpected {found}"));
+                match context.as_str() {
+                    "Statement" => diagnostic.with_note("statements end with a line break or `;`"),
+                    _ => diagnostic,
+                }
+            }
+            ParseError::UnExpectedEof(_) => {
+                Diagnostic::error("unexpected end of input").with_label(span, "the input ends here")
+            }
+            ParseError::OutOfRange(number, _) => {
+                let diagnostic = Diagnostic::error(format!("number `{number}` is out of range"))
+                    .with_label(span, "");
+                match Number::parse(number.trim_start_matches('-')) {
+                    Some(number) => diagnostic.with_note(number.range_note()),
+                    None => diagnostic,
+                }
+            }
+            ParseError::Unclosed(kind, _) => {
+                let diagnostic = Diagnostic::error(format!("unclosed {kind}"))
+                    .with_label(span, "opened here, but never closed");
+                match closing(*kind) {
+                    Some(close) => diagnostic.with_help(format!("add the matching {close}")),
+                    None => diagnostic,
+                }
+            }
+            ParseError::Lex(err) => err.diagnostic(),
+        }
+    }
+
+    fn span_mut(&mut self) -> &mut Span {
+        match self {
+            ParseError::Expected(_, _, span) => span,
+            ParseError::UnExpected(_, token) => &mut token.span,
+            ParseError::UnExpectedEof(span) => span,
+            ParseError::OutOfRange(_, span) => span,
+            ParseError::Unclosed(_, span) => span,
+            ParseError::Lex(err) => err.span_mut(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.diagnostic().message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// What the parser was working on, as the name the error was created with.
+fn describe(context: &str) -> &str {
+    match context {
+        "Assignment" => "assignment",
+        "Block" => "block",
+        "Call" => "call arguments",
+        "Expression" => "expression",
+        "For" => "for loop",
+        "Function" => "function definition",
+        "If" => "if expression",
+        "Lambda" => "lambda parameters",
+        "Interpolation" => "string interpolation",
+        "List" => "list",
+        "Match" => "match expression",
+        "Param" => "parameter list",
+        "Pattern" => "pattern",
+        "Statement" => "statement",
+        "Type" => "type",
+        "Value" => "value",
+        "Variable" => "variable declaration",
+        "While" => "while loop",
+        context => context,
+    }
+}
+
+fn closing(open: TokenKind) -> Option<TokenKind> {
+    match open {
+        TokenKind::LeftBrace => Some(TokenKind::RightBrace),
+        TokenKind::LeftBracket => Some(TokenKind::RightBracket),
+        TokenKind::LeftCurlyBracket => Some(TokenKind::RightCurlyBracket),
+        TokenKind::Pipe => Some(TokenKind::Pipe),
+        _ => None,
+    }
+}
+
+/// Reads and parses the file at `path`.
+pub fn parse_file(path: impl AsRef<Path>) -> io::Result<Result<ast::Ast, Vec<ParseError>>> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+    Ok(Parser::from_lexer(Lexer::new(&source))
+        .with_path(path)
+        .parse())
 }
 
 type ParseResult = Result<ast::Stmt, ParseError>;
@@ -22,158 +159,1140 @@ type ParseFn = fn(parser: &mut Parser) -> ParseResult;
 type ParseFnGen<T> = fn(parser: &mut Parser) -> ParseResultGen<T>;
 
 fn param(parser: &mut Parser) -> ParseResultGen<ast::Param> {
+    let start = parser.position;
     let Some(Token {
         kind: TokenKind::Ident,
-        value: name,
+        symbol: Some(name),
+        ..
     }) = parser.next_token()
     else {
-        return Err(ParseError::Expected("Param".into(), TokenKind::Ident));
+        return Err(ParseError::Expected(
+            "Param".into(),
+            TokenKind::Ident,
+            parser.last_span(),
+        ));
     };
-    let type_anot = type_anot(parser).map_or(None, |x| Some(x));
+    let type_anot = opt_type_anot(parser)?;
+    parser.node(SyntaxKind::Param, start);
+    match parser.peek() {
+        Some(Token {
+            kind: TokenKind::Comma,
+            ..
+        }) => {
+            parser.next_token();
+        }
+        Some(Token {
+            kind: TokenKind::RightBrace,
+            ..
+        }) => {}
+        Some(token) => {
+            return Err(ParseError::Expected(
+                "Param".into(),
+                TokenKind::Comma,
+                token.span,
+            ))
+        }
+        None => return Err(ParseError::UnExpectedEof(parser.eof_span())),
+    }
+    Ok((name, type_anot))
+}
+
+/// Precedence of the binary operators, all of which are left associative. Higher binds tighter.
+/// Ranges (`..`, `..=`) come below all of them, see [`expr`].
+///
+/// | operators              | precedence |
+/// |------------------------|------------|
+/// | `==` `<` `<=` `>` `>=` | 1          |
+/// | `+` `-`                | 2          |
+/// | `*` `/`                | 3          |
+/// | prefix `-` `!`         | 4          |
+/// | calls `f(x)`           | 5          |
+fn infix(op: Operator) -> Option<(ast::Operation, u8)> {
+    Some(match op {
+        Operator::EqEq => (ast::Operation::EqEq, 1),
+        Operator::Less => (ast::Operation::Less, 1),
+        Operator::LessEq => (ast::Operation::LessEq, 1),
+        Operator::More => (ast::Operation::More, 1),
+        Operator::MoreEq => (ast::Operation::MoreEq, 1),
+        Operator::Add => (ast::Operation::Add, 2),
+        Operator::Sub => (ast::Operation::Sub, 2),
+        Operator::Mul => (ast::Operation::Mul, 3),
+        Operator::Div => (ast::Operation::Div, 3),
+        Operator::Not => return None,
+    })
+}
+
+const PREFIX: u8 = 4;
+
+fn value(parser: &mut Parser) -> ParseResultGen<Value> {
+    signed_value(parser, None)
+}
+
+/// `minus` is the span of a `-` right in front of a number. It is part of the literal, so that
+/// `-128i8` is in range even though `128i8` isn't.
+fn signed_value(parser: &mut Parser, minus: Option<Span>) -> ParseResultGen<Value> {
+    match parser.next_token() {
+        Some(token) => {
+            let number = Number::parse(&token.value);
+            let span = minus.map_or(token.span, |minus| minus.to(token.span));
+            let out_of_range = || {
+                let sign = if minus.is_some() { "-" } else { "" };
+                ParseError::OutOfRange(format!("{sign}{}", token.value), span)
+            };
+            let kind = match token.kind {
+                // The lexer has already reported a malformed number, zero stands in for it.
+                TokenKind::Int if number.is_none() => ValueKind::Int(0),
+                TokenKind::Float if number.is_none() => ValueKind::Float(0.0),
+                TokenKind::Int => {
+                    let int = number.and_then(|number| match minus {
+                        Some(_) => number.negative_int(),
+                        None => number.int(),
+                    });
+                    ValueKind::Int(int.ok_or_else(out_of_range)?)
+                }
+                TokenKind::Float => {
+                    let float = number.and_then(|number| number.float());
+                    let float = float.ok_or_else(out_of_range)?;
+                    ValueKind::Float(if minus.is_some() { -float } else { float })
+                }
+                TokenKind::Ident if minus.is_none() => ValueKind::Ident(token.symbol.unwrap()),
+                TokenKind::String if minus.is_none() => ValueKind::String(token.value.into_owned()),
+                TokenKind::True if minus.is_none() => ValueKind::Bool(true),
+                TokenKind::False if minus.is_none() => ValueKind::Bool(false),
+                _ => return Err(ParseError::UnExpected("Value".into(), token.into_owned())),
+            };
+            Ok(Value::new(kind, span))
+        }
+        None => Err(ParseError::UnExpectedEof(parser.eof_span())),
+    }
+}
+
+fn binary_expr(lhs: ast::Expr, op: ast::Operation, rhs: ast::Expr) -> ast::Expr {
+    let span = lhs.span.to(rhs.span);
+    ast::Expr::new(ast::ExprKind::Binary(lhs.into(), op, rhs.into()), span)
+}
+
+fn type_anot(parser: &mut Parser) -> ParseResultGen<ast::Type> {
     let Some(Token {
-        kind: TokenKind::Comma,
+        kind: TokenKind::Colon,
         ..
     }) = parser.next_token()
     else {
-        return Err(ParseError::Expected("Param".into(), TokenKind::Comma));
+        return Err(ParseError::Expected(
+            "Type".into(),
+            TokenKind::Colon,
+            parser.last_span(),
+        ));
     };
-    return Ok((name, type_anot));
+    type_expr(parser)
+}
+
+fn opt_type_anot(parser: &mut Parser) -> ParseResultGen<Option<ast::Type>> {
+    match parser.peek() {
+        Some(Token {
+            kind: TokenKind::Colon,
+            ..
+        }) => type_anot(parser).map(Some),
+        _ => Ok(None),
+    }
 }
 
-fn operation(parser: &mut Parser) -> ParseResultGen<ast::Operation> {
+/// `int`, `float`, `string`, `bool`, `[T]`, `(T, U)` and `(T, U) -> V`. `()` is the empty tuple,
+/// `(T)` is just `T` and `(T,)` a tuple of one.
+fn type_expr(parser: &mut Parser) -> ParseResultGen<ast::Type> {
     match parser.next_token() {
-        Some(token) => {
-            if let TokenKind::Op(op_kind) = token.kind {
-                Ok(match op_kind {
-                    token::Operator::Add => ast::Operation::Add,
-                    token::Operator::Sub => ast::Operation::Sub,
-                    token::Operator::Mul => ast::Operation::Mul,
-                    token::Operator::Div => ast::Operation::Div,
-                    token::Operator::EqEq => ast::Operation::EqEq,
-                    token::Operator::More => ast::Operation::More,
-                    token::Operator::MoreEq => ast::Operation::MoreEq,
-                    token::Operator::Less => ast::Operation::Less,
-                    token::Operator::LessEq => ast::Operation::LessEq,
-                })
-            } else {
-                Err(ParseError::UnExpected("Operation".into(), token))
+        Some(token) if token.kind == TokenKind::Ident => match token.value.as_ref() {
+            "int" => Ok(ast::Type::Int),
+            "float" => Ok(ast::Type::Float),
+            "string" => Ok(ast::Type::String),
+            "bool" => Ok(ast::Type::Bool),
+            _ => Err(ParseError::UnExpected("Type".into(), token.into_owned())),
+        },
+        Some(Token {
+            kind: TokenKind::LeftBracket,
+            ..
+        }) => {
+            let elem = type_expr(parser)?;
+            let Some(Token {
+                kind: TokenKind::RightBracket,
+                ..
+            }) = parser.next_token()
+            else {
+                return Err(ParseError::Expected(
+                    "Type".into(),
+                    TokenKind::RightBracket,
+                    parser.last_span(),
+                ));
+            };
+            Ok(ast::Type::List(Box::new(elem)))
+        }
+        Some(Token {
+            kind: TokenKind::LeftBrace,
+            ..
+        }) => {
+            let mut types = Vec::new();
+            let mut trailing_comma = false;
+            loop {
+                match parser.peek() {
+                    Some(Token {
+                        kind: TokenKind::RightBrace,
+                        ..
+                    }) => {
+                        parser.next_token();
+                        break;
+                    }
+                    Some(_) => types.push(type_expr(parser)?),
+                    None => return Err(ParseError::UnExpectedEof(parser.eof_span())),
+                }
+                match parser.next_token() {
+                    Some(Token {
+                        kind: TokenKind::Comma,
+                        ..
+                    }) => trailing_comma = true,
+                    Some(Token {
+                        kind: TokenKind::RightBrace,
+                        ..
+                    }) => {
+                        trailing_comma = false;
+                        break;
+                    }
+                    Some(token) => {
+                        return Err(ParseError::UnExpected("Type".into(), token.into_owned()))
+                    }
+                    None => return Err(ParseError::UnExpectedEof(parser.eof_span())),
+                }
+            }
+
+            if let Some(Token {
+                kind: TokenKind::RightArrow,
+                ..
+            }) = parser.peek()
+            {
+                parser.next_token();
+                let ret = type_expr(parser)?;
+                return Ok(ast::Type::Func(types, Box::new(ret)));
+            }
+            match types.len() {
+                1 if !trailing_comma => Ok(types.pop().unwrap()),
+                _ => Ok(ast::Type::Tuple(types)),
             }
         }
-        None => Err(ParseError::UnExpectedEof),
+        Some(token) => Err(ParseError::UnExpected("Type".into(), token.into_owned())),
+        None => Err(ParseError::UnExpectedEof(parser.eof_span())),
     }
 }
 
-fn value(parser: &mut Parser) -> ParseResultGen<Value> {
-    match parser.next_token() {
-        Some(token) => match token.kind {
-            TokenKind::Int => Ok(Value::Int(token.value.parse::<i64>().unwrap())),
-            TokenKind::Float => Ok(Value::Float(token.value.parse::<f64>().unwrap())),
-            TokenKind::Ident => Ok(Value::Ident(token.value)),
-            TokenKind::String => Ok(Value::String(token.value)),
-            _ => Err(ParseError::UnExpected("Value".into(), token)),
-        },
-        None => Err(ParseError::UnExpectedEof),
+fn parse_if(parser: &mut Parser) -> ParseResult {
+    let expr = if_expr(parser)?;
+    let span = expr.span;
+    Ok(ast::Stmt::new(ast::StmtKind::Expr(expr), span))
+}
+
+/// `if cond { .. } else if cond { .. } else { .. }`. Without an `else`, a false condition gives
+/// unit. The `else` may start the line after the `}` before it.
+fn if_expr(parser: &mut Parser) -> ParseResultGen<ast::Expr> {
+    let position = parser.position;
+    let Some(Token {
+        kind: TokenKind::If,
+        span: start,
+        ..
+    }) = parser.next_token()
+    else {
+        return Err(ParseError::Expected(
+            "If".into(),
+            TokenKind::If,
+            parser.last_span(),
+        ));
+    };
+    let cond = expr(parser)?;
+    let then = block(parser)?;
+    let kind = |token: Option<&Token>| token.map(|token| token.kind);
+    if kind(parser.peek()) == Some(TokenKind::Newline)
+        && kind(parser.peek_nth(1)) == Some(TokenKind::Else)
+    {
+        parser.next_token();
     }
+    let otherwise = match parser.peek() {
+        Some(Token {
+            kind: TokenKind::Else,
+            ..
+        }) => {
+            parser.next_token();
+            match parser.peek() {
+                Some(Token {
+                    kind: TokenKind::If,
+                    ..
+                }) => Some(if_expr(parser)?),
+                _ => Some(block(parser)?),
+            }
+        }
+        _ => None,
+    };
+    let end = otherwise.as_ref().unwrap_or(&then).span;
+    parser.node(SyntaxKind::If, position);
+    Ok(ast::Expr::new(
+        ast::ExprKind::If(cond.into(), then.into(), otherwise.map(Box::new)),
+        start.to(end),
+    ))
 }
 
-fn binary(parser: &mut Parser, start: ast::Expr) -> ParseResultGen<ast::Expr> {
-    // TODO: If there is an error in the binary expr this function will fail, not by error, but by
-    // shutting down execution, we must return errors instead (speaks for itself, but I mustn't
-    // forget.
-    let mut operations = LinkedList::new();
-    let mut values = LinkedList::from([start]);
+/// `while cond { .. }`, where the condition may be in parentheses like any expression.
+fn parse_while(parser: &mut Parser) -> ParseResult {
+    let Some(Token {
+        kind: TokenKind::While,
+        span: start,
+        ..
+    }) = parser.next_token()
+    else {
+        return Err(ParseError::Expected(
+            "While".into(),
+            TokenKind::While,
+            parser.last_span(),
+        ));
+    };
+    let cond = expr(parser)?;
+    let body = block(parser)?;
+    let span = start.to(body.span);
+    let body = ast::Stmt::new(ast::StmtKind::Expr(body), span);
+    Ok(ast::Stmt::new(
+        ast::StmtKind::While(cond, body.into()),
+        span,
+    ))
+}
 
+/// `(a)` is just `a`, while `()`, `(a,)` and `(a, b)` are tuples.
+fn paren(parser: &mut Parser) -> ParseResultGen<ast::Expr> {
+    let open = parser.next_token().unwrap();
+    let mut elems = Vec::new();
     loop {
-        let Ok(op) = parser.try_parse_gen(operation) else {
-            break;
-        };
-        let rhs = ast::Expr::Value(value(parser)?);
-
-        match op {
-            ast::Operation::Mul | ast::Operation::Div => {
-                let lhs = values.pop_back().unwrap();
-                values.push_back(ast::Expr::Binary(lhs.into(), op, rhs.into()));
+        match parser.peek() {
+            Some(Token {
+                kind: TokenKind::RightBrace,
+                ..
+            }) => break,
+            Some(_) => elems.push(expr(parser)?),
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
+        }
+        match parser.peek() {
+            Some(Token {
+                kind: TokenKind::Comma,
+                ..
+            }) => {
+                parser.next_token();
             }
-            op => {
-                values.push_back(rhs);
-                operations.push_back(op)
+            Some(Token {
+                kind: TokenKind::RightBrace,
+                ..
+            }) if elems.len() == 1 => {
+                parser.next_token();
+                return Ok(elems.pop().unwrap());
+            }
+            Some(Token {
+                kind: TokenKind::RightBrace,
+                ..
+            }) => break,
+            Some(token) => {
+                return Err(ParseError::UnExpected(
+                    "Expression".into(),
+                    token.clone().into_owned(),
+                ))
             }
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
         }
     }
+    let close = parser.next_token().unwrap().span;
+    Ok(ast::Expr::new(
+        ast::ExprKind::Tuple(elems),
+        open.span.to(close),
+    ))
+}
 
-    while let Some(op) = operations.pop_back() {
-        let rhs = values.pop_back().unwrap();
-        let lhs = values.pop_back().unwrap();
-        values.push_back(ast::Expr::Binary(lhs.into(), op, rhs.into()));
+/// `match x { pattern => expr, pattern if guard => { .. } }`. Arms are separated by commas or
+/// line breaks.
+fn match_expr(parser: &mut Parser) -> ParseResultGen<ast::Expr> {
+    let start = parser.next_token().unwrap().span;
+    let scrutinee = expr(parser)?;
+    let Some(open) = parser.next_token() else {
+        return Err(ParseError::UnExpectedEof(parser.eof_span()));
+    };
+    if open.kind != TokenKind::LeftCurlyBracket {
+        return Err(ParseError::Expected(
+            "Match".into(),
+            TokenKind::LeftCurlyBracket,
+            open.span,
+        ));
     }
 
-    assert!(values.len() == 1);
-    Ok(values.pop_front().unwrap())
+    let mut cases = Vec::new();
+    let end = loop {
+        while let Some(Token {
+            kind: TokenKind::Comma | TokenKind::Newline,
+            ..
+        }) = parser.peek()
+        {
+            parser.next_token();
+        }
+        match parser.peek() {
+            Some(Token {
+                kind: TokenKind::RightCurlyBracket,
+                span,
+                ..
+            }) => {
+                let span = *span;
+                parser.next_token();
+                break span;
+            }
+            Some(_) => cases.push(case(parser)?),
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
+        }
+        match parser.peek() {
+            Some(Token {
+                kind: TokenKind::Comma | TokenKind::Newline | TokenKind::RightCurlyBracket,
+                ..
+            }) => {}
+            Some(token) => {
+                return Err(ParseError::UnExpected(
+                    "Match".into(),
+                    token.clone().into_owned(),
+                ))
+            }
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
+        }
+    };
+    Ok(ast::Expr::new(
+        ast::ExprKind::Match(scrutinee.into(), cases),
+        start.to(end),
+    ))
 }
 
-fn type_anot(parser: &mut Parser) -> ParseResultGen<ast::Type> {
-    todo!()
+fn case(parser: &mut Parser) -> ParseResultGen<ast::Case> {
+    let start = parser.position;
+    let pattern = pattern(parser)?;
+    let guard = match parser.peek() {
+        Some(Token {
+            kind: TokenKind::If,
+            ..
+        }) => {
+            parser.next_token();
+            Some(expr(parser)?)
+        }
+        _ => None,
+    };
+    let Some(Token {
+        kind: TokenKind::FatArrow,
+        ..
+    }) = parser.next_token()
+    else {
+        return Err(ParseError::Expected(
+            "Match".into(),
+            TokenKind::FatArrow,
+            parser.last_span(),
+        ));
+    };
+    let body = match parser.peek() {
+        Some(Token {
+            kind: TokenKind::LeftCurlyBracket,
+            ..
+        }) => block(parser)?,
+        _ => expr(parser)?,
+    };
+    parser.node(SyntaxKind::MatchArm, start);
+    Ok((pattern, guard, body))
 }
 
-fn function_call(parser: &mut Parser, value: Value) -> ParseResultGen<ast::Expr> {
+/// One or more alternatives separated by `|`.
+fn pattern(parser: &mut Parser) -> ParseResultGen<ast::Pattern> {
+    let start = parser.position;
+    let first = single_pattern(parser)?;
+    let mut alternatives = vec![first];
+    while let Some(Token {
+        kind: TokenKind::Pipe,
+        ..
+    }) = parser.peek()
+    {
+        parser.next_token();
+        alternatives.push(single_pattern(parser)?);
+    }
+    parser.node(SyntaxKind::Pattern, start);
+    if alternatives.len() == 1 {
+        return Ok(alternatives.pop().unwrap());
+    }
+    let span = alternatives[0].span.to(alternatives.last().unwrap().span);
+    Ok(ast::Pattern::new(ast::PatternKind::Or(alternatives), span))
+}
+
+fn single_pattern(parser: &mut Parser) -> ParseResultGen<ast::Pattern> {
+    let Some(token) = parser.peek() else {
+        return Err(ParseError::UnExpectedEof(parser.eof_span()));
+    };
+    let span = token.span;
+    match token.kind {
+        TokenKind::Ident if token.value == "_" => {
+            parser.next_token();
+            Ok(ast::Pattern::new(ast::PatternKind::Wildcard, span))
+        }
+        TokenKind::Ident => {
+            let name = parser.next_token().unwrap().symbol.unwrap();
+            match parser.peek() {
+                Some(Token {
+                    kind: TokenKind::LeftCurlyBracket,
+                    ..
+                }) => struct_pattern(parser, name, span),
+                _ => Ok(ast::Pattern::new(ast::PatternKind::Binding(name), span)),
+            }
+        }
+        TokenKind::LeftBrace => {
+            let (mut patterns, trailing_comma, span) = pattern_list(parser, TokenKind::RightBrace)?;
+            if patterns.len() == 1 && !trailing_comma {
+                return Ok(patterns.pop().unwrap());
+            }
+            Ok(ast::Pattern::new(ast::PatternKind::Tuple(patterns), span))
+        }
+        TokenKind::LeftBracket => {
+            let (patterns, _, span) = pattern_list(parser, TokenKind::RightBracket)?;
+            Ok(ast::Pattern::new(ast::PatternKind::List(patterns), span))
+        }
+        TokenKind::DotDot => {
+            parser.next_token();
+            Ok(ast::Pattern::new(ast::PatternKind::Rest, span))
+        }
+        _ => {
+            let start = literal(parser)?;
+            let limits = match parser.peek() {
+                Some(Token {
+                    kind: TokenKind::DotDot,
+                    ..
+                }) => ast::RangeLimits::Exclusive,
+                Some(Token {
+                    kind: TokenKind::DotDotEq,
+                    ..
+                }) => ast::RangeLimits::Inclusive,
+                _ => {
+                    let span = start.span;
+                    return Ok(ast::Pattern::new(ast::PatternKind::Literal(start), span));
+                }
+            };
+            parser.next_token();
+            let end = literal(parser)?;
+            let span = start.span.to(end.span);
+            Ok(ast::Pattern::new(
+                ast::PatternKind::Range(start, end, limits),
+                span,
+            ))
+        }
+    }
+}
+
+/// A number, possibly negative, or a string.
+fn literal(parser: &mut Parser) -> ParseResultGen<Value> {
+    let minus = match parser.peek() {
+        Some(Token {
+            kind: TokenKind::Op(Operator::Sub),
+            span,
+            ..
+        }) => {
+            let span = *span;
+            parser.next_token();
+            Some(span)
+        }
+        _ => None,
+    };
     match parser.peek() {
-        Some(t) => match t.kind {
-            TokenKind::LeftBrace => {
-                parser.next_token().unwrap();
-                todo!()
+        Some(Token {
+            kind: TokenKind::Int | TokenKind::Float,
+            ..
+        }) => {}
+        Some(Token {
+            kind: TokenKind::String | TokenKind::True | TokenKind::False,
+            ..
+        }) if minus.is_none() => {}
+        Some(token) => {
+            return Err(ParseError::UnExpected(
+                "Pattern".into(),
+                token.clone().into_owned(),
+            ))
+        }
+        None => return Err(ParseError::UnExpectedEof(parser.eof_span())),
+    }
+    signed_value(parser, minus)
+}
+
+/// Comma separated patterns up to `close`. Also returns whether there was a trailing comma.
+fn pattern_list(
+    parser: &mut Parser,
+    close: TokenKind,
+) -> ParseResultGen<(Vec<ast::Pattern>, bool, Span)> {
+    let open = parser.next_token().unwrap();
+    let mut patterns = Vec::new();
+    let mut trailing_comma = false;
+    let end = loop {
+        match parser.peek() {
+            Some(token) if token.kind == close => {
+                let span = token.span;
+                parser.next_token();
+                break span;
             }
-            _ => unreachable!("This should never happen, function call should only be called if the next token is a leftbrace.")
-        },
-        _ => unreachable!("This should never happen, function call should only be called if the next token is a leftbrace.")
+            Some(_) => patterns.push(pattern(parser)?),
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
+        }
+        match parser.peek() {
+            Some(Token {
+                kind: TokenKind::Comma,
+                ..
+            }) => {
+                parser.next_token();
+                trailing_comma = true;
+            }
+            Some(token) if token.kind == close => trailing_comma = false,
+            Some(token) => {
+                return Err(ParseError::UnExpected(
+                    "Pattern".into(),
+                    token.clone().into_owned(),
+                ))
+            }
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
+        }
+    };
+    Ok((patterns, trailing_comma, open.span.to(end)))
+}
+
+/// The `{ field, other: pattern }` after a struct name. A field on its own binds its value to a
+/// variable of the same name.
+fn struct_pattern(
+    parser: &mut Parser,
+    name: ast::Symbol,
+    start: Span,
+) -> ParseResultGen<ast::Pattern> {
+    let open = parser.next_token().unwrap();
+    let mut fields = Vec::new();
+    let end = loop {
+        match parser.next_token() {
+            Some(Token {
+                kind: TokenKind::RightCurlyBracket,
+                span,
+                ..
+            }) => break span,
+            Some(Token {
+                kind: TokenKind::Ident,
+                symbol: Some(field),
+                span,
+                ..
+            }) => {
+                let pattern = match parser.peek() {
+                    Some(Token {
+                        kind: TokenKind::Colon,
+                        ..
+                    }) => {
+                        parser.next_token();
+                        pattern(parser)?
+                    }
+                    _ => ast::Pattern::new(ast::PatternKind::Binding(field.clone()), span),
+                };
+                fields.push((field, pattern));
+            }
+            Some(token) => {
+                return Err(ParseError::UnExpected("Pattern".into(), token.into_owned()))
+            }
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
+        }
+        match parser.peek() {
+            Some(Token {
+                kind: TokenKind::Comma,
+                ..
+            }) => {
+                parser.next_token();
+            }
+            Some(Token {
+                kind: TokenKind::RightCurlyBracket,
+                ..
+            }) => {}
+            Some(token) => {
+                return Err(ParseError::UnExpected(
+                    "Pattern".into(),
+                    token.clone().into_owned(),
+                ))
+            }
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
+        }
+    };
+    Ok(ast::Pattern::new(
+        ast::PatternKind::Struct(name, fields),
+        start.to(end),
+    ))
+}
+
+/// An expression, or an assignment to it if it is followed by `=`, `+=` and the like.
+fn expr_stmt(parser: &mut Parser) -> ParseResult {
+    let target = expr(parser)?;
+    let op = match parser.peek() {
+        Some(Token {
+            kind: TokenKind::Eq,
+            ..
+        }) => None,
+        Some(Token {
+            kind: TokenKind::AssignOp(op),
+            ..
+        }) => infix(*op).map(|(op, _)| op),
+        _ => {
+            let span = target.span;
+            return Ok(ast::Stmt::new(ast::StmtKind::Expr(target), span));
+        }
+    };
+    parser.next_token();
+    if !matches!(
+        target.kind,
+        ast::ExprKind::Value(Value {
+            kind: ValueKind::Ident(_),
+            ..
+        })
+    ) {
+        return Err(ParseError::Expected(
+            "Assignment".into(),
+            TokenKind::Ident,
+            target.span,
+        ));
+    }
+    let value = expr(parser)?;
+    let span = target.span.to(value.span);
+    Ok(ast::Stmt::new(
+        ast::StmtKind::Assign(target, op, value),
+        span,
+    ))
+}
+
+/// `for (x <= iter) { .. }`, with the parentheses being optional. `iter` is a range, a list or a
+/// string.
+fn parse_for(parser: &mut Parser) -> ParseResult {
+    let Some(Token {
+        kind: TokenKind::For,
+        span: start,
+        ..
+    }) = parser.next_token()
+    else {
+        return Err(ParseError::Expected(
+            "For".into(),
+            TokenKind::For,
+            parser.last_span(),
+        ));
+    };
+    let parens = match parser.peek() {
+        Some(Token {
+            kind: TokenKind::LeftBrace,
+            ..
+        }) => parser.next_token(),
+        _ => None,
+    };
+    let Some(Token {
+        kind: TokenKind::Ident,
+        symbol: Some(name),
+        ..
+    }) = parser.next_token()
+    else {
+        return Err(ParseError::Expected(
+            "For".into(),
+            TokenKind::Ident,
+            parser.last_span(),
+        ));
+    };
+    let Some(Token {
+        kind: TokenKind::Op(Operator::LessEq),
+        ..
+    }) = parser.next_token()
+    else {
+        return Err(ParseError::Expected(
+            "For".into(),
+            TokenKind::Op(Operator::LessEq),
+            parser.last_span(),
+        ));
+    };
+    let iter = expr(parser)?;
+    if let Some(open) = parens {
+        match parser.next_token() {
+            Some(Token {
+                kind: TokenKind::RightBrace,
+                ..
+            }) => {}
+            Some(token) => return Err(ParseError::UnExpected("For".into(), token.into_owned())),
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
+        }
+    }
+    let body = block(parser)?;
+    let span = start.to(body.span);
+    let body = ast::Stmt::new(ast::StmtKind::Expr(body), span);
+    Ok(ast::Stmt::new(
+        ast::StmtKind::For(name, iter, body.into()),
+        span,
+    ))
+}
+
+/// `{` statements `}`, a scope of its own. Function, loop and `if` bodies are blocks too.
+fn block(parser: &mut Parser) -> ParseResultGen<ast::Expr> {
+    let position = parser.position;
+    let Some(Token {
+        kind: TokenKind::LeftCurlyBracket,
+        span: open,
+        ..
+    }) = parser.next_token()
+    else {
+        return Err(ParseError::Expected(
+            "Block".into(),
+            TokenKind::LeftCurlyBracket,
+            parser.last_span(),
+        ));
+    };
+    let mut stmts = Vec::new();
+    loop {
+        parser.separators();
+        match parser.peek() {
+            Some(Token {
+                kind: TokenKind::RightCurlyBracket,
+                span: close,
+                ..
+            }) => {
+                let span = open.to(*close);
+                parser.next_token();
+                parser.node(SyntaxKind::Block, position);
+                return Ok(ast::Expr::new(ast::ExprKind::Block(stmts), span));
+            }
+            Some(_) => {
+                let start = parser.position;
+                match parser.parse_stmt() {
+                    Some(Ok(stmt)) => stmts.push(stmt),
+                    Some(Err(err)) => {
+                        let span = parser.recover(err, start);
+                        parser.node(SyntaxKind::Error, start);
+                        stmts.push(ast::Stmt::new(ast::StmtKind::Error, span));
+                    }
+                    None => return Err(ParseError::Unclosed(TokenKind::LeftCurlyBracket, open)),
+                }
+            }
+            None => return Err(ParseError::Unclosed(TokenKind::LeftCurlyBracket, open)),
+        }
+    }
+}
+
+/// Parses a comma separated list of expressions between the next token and `close`, allowing a
+/// trailing comma. Returns the expressions and the span of the whole list.
+fn expr_list(
+    parser: &mut Parser,
+    name: &str,
+    close: TokenKind,
+) -> ParseResultGen<(Vec<ast::Expr>, Span)> {
+    let open = parser.next_token().unwrap();
+    let mut exprs = Vec::new();
+    let end = loop {
+        match parser.peek() {
+            Some(token) if token.kind == close => {
+                let span = token.span;
+                parser.next_token();
+                break span;
+            }
+            Some(_) => exprs.push(expr(parser)?),
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
+        }
+        match parser.peek() {
+            Some(Token {
+                kind: TokenKind::Comma,
+                ..
+            }) => {
+                parser.next_token();
+            }
+            Some(token) if token.kind == close => {}
+            Some(token) => {
+                return Err(ParseError::UnExpected(
+                    name.into(),
+                    token.clone().into_owned(),
+                ))
+            }
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
+        }
+    };
+    Ok((exprs, open.span.to(end)))
+}
+
+/// Parses the argument list after `callee`.
+fn function_call(parser: &mut Parser, callee: ast::Expr) -> ParseResultGen<ast::Expr> {
+    let (args, list) = expr_list(parser, "Call", TokenKind::RightBrace)?;
+    let span = callee.span.to(list);
+    Ok(ast::Expr::new(
+        ast::ExprKind::Call(callee.into(), args),
+        span,
+    ))
+}
+
+fn interpolation(parser: &mut Parser) -> ParseResultGen<ast::Expr> {
+    let Some(Token {
+        kind: TokenKind::InterpStart,
+        value,
+        span: start,
+        ..
+    }) = parser.next_token()
+    else {
+        return Err(ParseError::Expected(
+            "Interpolation".into(),
+            TokenKind::InterpStart,
+            parser.last_span(),
+        ));
+    };
+
+    let mut parts = vec![ast::StringPart::Literal(value.into_owned())];
+    loop {
+        parts.push(ast::StringPart::Expr(expr(parser)?));
+        match parser.next_token() {
+            Some(Token {
+                kind: TokenKind::InterpMid,
+                value,
+                ..
+            }) => parts.push(ast::StringPart::Literal(value.into_owned())),
+            Some(Token {
+                kind: TokenKind::InterpEnd,
+                value,
+                span: end,
+                ..
+            }) => {
+                parts.push(ast::StringPart::Literal(value.into_owned()));
+                parts.retain(|part| !matches!(part, ast::StringPart::Literal(s) if s.is_empty()));
+                let kind = ast::ExprKind::Interpolation(parts);
+                return Ok(ast::Expr::new(kind, start.to(end)));
+            }
+            Some(token) => {
+                return Err(ParseError::UnExpected(
+                    "Interpolation".into(),
+                    token.into_owned(),
+                ))
+            }
+            None => return Err(ParseError::UnExpectedEof(parser.eof_span())),
+        }
+    }
+}
+
+/// A range binds looser than any operator and doesn't chain, so `0 .. n + 1` needs no
+/// parentheses.
+fn expr(parser: &mut Parser) -> ParseResultGen<ast::Expr> {
+    let position = parser.position;
+    let start = expr_bp(parser, 0)?;
+    let limits = match parser.peek() {
+        Some(Token {
+            kind: TokenKind::DotDot,
+            ..
+        }) => ast::RangeLimits::Exclusive,
+        Some(Token {
+            kind: TokenKind::DotDotEq,
+            ..
+        }) => ast::RangeLimits::Inclusive,
+        _ => return Ok(start),
+    };
+    parser.next_token();
+    let end = expr_bp(parser, 0)?;
+    // `step` is only a keyword here, so it can still be used as a name elsewhere.
+    let step = match parser.peek() {
+        Some(token) if token.kind == TokenKind::Ident && token.value == "step" => {
+            parser.next_token();
+            Some(Box::new(expr_bp(parser, 0)?))
+        }
+        _ => None,
+    };
+    let span = start.span.to(step.as_deref().unwrap_or(&end).span);
+    parser.node(SyntaxKind::Range, position);
+    Ok(ast::Expr::new(
+        ast::ExprKind::Range(start.into(), end.into(), limits, step),
+        span,
+    ))
+}
+
+/// Parses an expression whose binary operators all bind tighter than `min`.
+fn expr_bp(parser: &mut Parser, min: u8) -> ParseResultGen<ast::Expr> {
+    let start = parser.position;
+    let mut lhs = prefix(parser)?;
+    while let Some(Token {
+        kind: TokenKind::Op(op),
+        ..
+    }) = parser.peek()
+    {
+        let Some((op, precedence)) = infix(*op) else {
+            break;
+        };
+        // Stopping at equal precedence makes the caller fold `a - b - c` as `(a - b) - c`.
+        if precedence <= min {
+            break;
+        }
+        parser.next_token();
+        let rhs = expr_bp(parser, precedence)?;
+        parser.node(SyntaxKind::Binary, start);
+        lhs = binary_expr(lhs, op, rhs);
+    }
+    Ok(lhs)
+}
+
+fn prefix(parser: &mut Parser) -> ParseResultGen<ast::Expr> {
+    let op = match parser.peek() {
+        Some(Token {
+            kind: TokenKind::Op(Operator::Sub),
+            ..
+        }) => ast::UnaryOp::Neg,
+        Some(Token {
+            kind: TokenKind::Op(Operator::Not),
+            ..
+        }) => ast::UnaryOp::Not,
+        _ => return postfix(parser),
+    };
+    let position = parser.position;
+    let start = parser.next_token().unwrap().span;
+    // A negative number is a literal of its own, rather than the negation of a positive one that
+    // might be out of range.
+    if let (
+        ast::UnaryOp::Neg,
+        Some(Token {
+            kind: TokenKind::Int | TokenKind::Float,
+            ..
+        }),
+    ) = (op, parser.peek())
+    {
+        let value = signed_value(parser, Some(start))?;
+        parser.node(SyntaxKind::Literal, position);
+        return Ok(value.into());
+    }
+    let operand = expr_bp(parser, PREFIX)?;
+    parser.node(SyntaxKind::Unary, position);
+    let span = start.to(operand.span);
+    Ok(ast::Expr::new(
+        ast::ExprKind::Unary(op, operand.into()),
+        span,
+    ))
+}
+
+fn postfix(parser: &mut Parser) -> ParseResultGen<ast::Expr> {
+    let start = parser.position;
+    let mut expr = primary(parser)?;
+    while let Some(Token {
+        kind: TokenKind::LeftBrace,
+        ..
+    }) = parser.peek()
+    {
+        let args = parser.position;
+        expr = function_call(parser, expr)?;
+        parser.node(SyntaxKind::ArgList, args);
+        parser.node(SyntaxKind::Call, start);
     }
+    Ok(expr)
 }
 
-fn expr(parser: &mut Parser) -> ParseResultGen<ast::Expr> {
-    match parser.peek() {
+/// `if` and blocks are also parsed elsewhere, so they add their syntax nodes themselves.
+fn primary(parser: &mut Parser) -> ParseResultGen<ast::Expr> {
+    let start = parser.position;
+    let (kind, expr) = match parser.peek() {
         Some(token) => match token.kind {
-            TokenKind::Int | TokenKind::Float | TokenKind::String | TokenKind::Ident => {
-                let value = value(parser)?;
-
-                match parser.peek() {
-                    Some(t) => match t.kind {
-                        TokenKind::Op(_) => binary(parser, ast::Expr::Value(value)),
-                        TokenKind::LeftBrace => function_call(parser, value),
-                        _ => Ok(ast::Expr::Value(value)),
-                    },
-                    None => Ok(ast::Expr::Value(value)),
-                }
+            TokenKind::InterpStart => (SyntaxKind::Interpolation, interpolation(parser)?),
+            TokenKind::If => return if_expr(parser),
+            TokenKind::LeftBracket => {
+                let (elems, span) = expr_list(parser, "List", TokenKind::RightBracket)?;
+                let list = ast::Expr::new(ast::ExprKind::List(elems), span);
+                (SyntaxKind::List, list)
+            }
+            TokenKind::Ident => (SyntaxKind::Name, value(parser)?.into()),
+            TokenKind::Int
+            | TokenKind::Float
+            | TokenKind::String
+            | TokenKind::True
+            | TokenKind::False => (SyntaxKind::Literal, value(parser)?.into()),
+            TokenKind::LeftBrace => (SyntaxKind::Paren, paren(parser)?),
+            TokenKind::Match => (SyntaxKind::Match, match_expr(parser)?),
+            TokenKind::LeftCurlyBracket => return block(parser),
+            TokenKind::Pipe => (SyntaxKind::Lambda, lambda(parser)?),
+            _ => {
+                return Err(ParseError::UnExpected(
+                    "Expression".into(),
+                    token.clone().into_owned(),
+                ))
             }
-            _kind => Err(ParseError::UnExpected("Expression".into(), token)),
         },
-        None => Err(ParseError::UnExpectedEof),
+        None => return Err(ParseError::UnExpectedEof(parser.eof_span())),
+    };
+    parser.node(kind, start);
+    Ok(expr)
+}
+
+/// `|a, b: int| body`. The body reaches as far as an expression can, so `|x| x + 1` adds inside
+/// the function.
+fn lambda(parser: &mut Parser) -> ParseResultGen<ast::Expr> {
+    let start = parser.position;
+    let open = parser.next_token().unwrap();
+    let mut params = Vec::new();
+    loop {
+        let param = parser.position;
+        match parser.next_token() {
+            Some(Token {
+                kind: TokenKind::Pipe,
+                ..
+            }) => break,
+            Some(Token {
+                kind: TokenKind::Ident,
+                symbol: Some(name),
+                ..
+            }) => {
+                params.push((name, opt_type_anot(parser)?));
+                parser.node(SyntaxKind::Param, param);
+            }
+            Some(token) => {
+                return Err(ParseError::Expected(
+                    "Lambda".into(),
+                    TokenKind::Ident,
+                    token.span,
+                ))
+            }
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
+        }
+        match parser.next_token() {
+            Some(Token {
+                kind: TokenKind::Comma,
+                ..
+            }) => {}
+            Some(Token {
+                kind: TokenKind::Pipe,
+                ..
+            }) => break,
+            Some(token) => {
+                return Err(ParseError::Expected(
+                    "Lambda".into(),
+                    TokenKind::Pipe,
+                    token.span,
+                ))
+            }
+            None => return Err(ParseError::Unclosed(open.kind, open.span)),
+        }
     }
+    parser.node(SyntaxKind::ParamList, start);
+    let body = expr(parser)?;
+    let span = open.span.to(body.span);
+    Ok(ast::Expr::new(
+        ast::ExprKind::Lambda(params, body.into()),
+        span,
+    ))
 }
 
 fn params(parser: &mut Parser) -> ParseResultGen<ast::Params> {
+    let start = parser.position;
     let Some(Token {
         kind: TokenKind::LeftBrace,
         ..
     }) = parser.next_token()
     else {
-        return Err(ParseError::Expected("Variable".into(), TokenKind::Ident));
+        return Err(ParseError::Expected(
+            "Function".into(),
+            TokenKind::LeftBrace,
+            parser.last_span(),
+        ));
     };
 
     let mut params = Vec::new();
     loop {
         match parser.peek() {
-            Some(Token {
-                kind: TokenKind::Comma,
-                ..
-            }) => {
-                parser.next_token();
-                params.push(param(parser)?);
-            }
             Some(Token {
                 kind: TokenKind::RightBrace,
                 ..
             }) => break,
-            Some(token) => return Err(ParseError::UnExpected("Params".into(), token)),
-            None => return Err(ParseError::UnExpectedEof),
+            Some(_) => params.push(param(parser)?),
+            None => return Err(ParseError::UnExpectedEof(parser.eof_span())),
         }
     }
 
@@ -182,120 +1301,449 @@ fn params(parser: &mut Parser) -> ParseResultGen<ast::Params> {
         ..
     }) = parser.next_token()
     else {
-        return Err(ParseError::Expected("Variable".into(), TokenKind::Ident));
+        return Err(ParseError::Expected(
+            "Param".into(),
+            TokenKind::RightBrace,
+            parser.last_span(),
+        ));
     };
-
+    parser.node(SyntaxKind::ParamList, start);
     Ok(params)
 }
 
 fn var(parser: &mut Parser) -> ParseResult {
     let Some(Token {
         kind: TokenKind::Let,
+        span: start,
         ..
     }) = parser.next_token()
     else {
-        panic!("Var was called even tho their is no Let.")
+        return Err(ParseError::Expected(
+            "Variable".into(),
+            TokenKind::Let,
+            parser.last_span(),
+        ));
+    };
+    let mutability = match parser.peek() {
+        Some(Token {
+            kind: TokenKind::Mut,
+            ..
+        }) => {
+            parser.next_token();
+            ast::Mutability::Mut
+        }
+        _ => ast::Mutability::Not,
     };
     let Some(Token {
-        value: var_name,
+        symbol: Some(var_name),
         kind: TokenKind::Ident,
+        ..
     }) = parser.next_token()
     else {
-        return Err(ParseError::Expected("Variable".into(), TokenKind::Ident));
+        return Err(ParseError::Expected(
+            "Variable".into(),
+            TokenKind::Ident,
+            parser.last_span(),
+        ));
     };
-    let typed = parser.try_parse_gen(type_anot).map_or(None, |x| Some(x));
+    let typed = opt_type_anot(parser)?;
     let Some(Token {
         kind: TokenKind::Eq,
         ..
     }) = parser.next_token()
     else {
-        return Err(ParseError::Expected("Variable".into(), TokenKind::Eq));
+        return Err(ParseError::Expected(
+            "Variable".into(),
+            TokenKind::Eq,
+            parser.last_span(),
+        ));
     };
-    let expr = expr(parser)?;
-    Ok(ast::Stmt::Let(var_name, typed, expr))
+    // The variable is still declared when its value is broken, so later passes don't trip over
+    // its uses.
+    let value_start = parser.position;
+    let expr = match expr(parser) {
+        Ok(expr) => expr,
+        Err(err) => {
+            let span = parser.recover(err, value_start);
+            parser.node(SyntaxKind::Error, value_start);
+            ast::Expr::new(ast::ExprKind::Error, span)
+        }
+    };
+    let span = start.to(expr.span);
+    Ok(ast::Stmt::new(
+        ast::StmtKind::Let(var_name, mutability, typed, expr),
+        span,
+    ))
 }
 
 fn func(parser: &mut Parser) -> ParseResult {
     let Some(Token {
         kind: TokenKind::Let,
+        span: start,
         ..
     }) = parser.next_token()
     else {
-        panic!("Func was called even tho their is no Let.")
+        return Err(ParseError::Expected(
+            "Function".into(),
+            TokenKind::Let,
+            parser.last_span(),
+        ));
     };
     let Some(Token {
-        value: func_name,
+        symbol: Some(func_name),
         kind: TokenKind::Ident,
+        ..
     }) = parser.next_token()
     else {
-        return Err(ParseError::Expected("Variable".into(), TokenKind::Ident));
+        return Err(ParseError::Expected(
+            "Variable".into(),
+            TokenKind::Ident,
+            parser.last_span(),
+        ));
     };
     let params = params(parser)?;
-    let typed = match parser.try_parse_gen(type_anot) {
-        Ok(typed) => Some(typed),
-        Err(_) => None,
+    let typed = opt_type_anot(parser)?;
+    let Some(Token {
+        kind: TokenKind::Eq,
+        ..
+    }) = parser.next_token()
+    else {
+        return Err(ParseError::Expected(
+            "Function".into(),
+            TokenKind::Eq,
+            parser.last_span(),
+        ));
     };
     let expr = expr(parser)?;
-    Ok(ast::Stmt::Func(func_name, params, typed, expr))
+    let span = start.to(expr.span);
+    Ok(ast::Stmt::new(
+        ast::StmtKind::Func(func_name, params, typed, expr),
+        span,
+    ))
 }
 
-impl Parser {
-    pub fn next_token(&mut self) -> Option<Token> {
+impl<'a> Parser<'a> {
+    /// Creates a parser that pulls tokens from `lexer` as it needs them.
+    pub fn from_lexer(lexer: Lexer<'a>) -> Self {
+        Self {
+            lexer: Some(lexer),
+            ..Self::new(Vec::new())
+        }
+    }
+
+    pub fn new(tokens: Vec<Token<'a>>) -> Self {
+        Self {
+            position: 0,
+            tokens,
+            lexer: None,
+            lex_errors: Vec::new(),
+            errors: Vec::new(),
+            nodes: Vec::new(),
+            name: None,
+            path: None,
+        }
+    }
+
+    /// Names the resulting `Ast` after the file, e.g. `main` for `src/main.monk`.
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        self.name = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned());
+        self.path = Some(path);
+        self
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn lex_errors(&self) -> &[LexError] {
+        &self.lex_errors
+    }
+
+    /// Pulls tokens from the lexer until the one `ahead` of `position` is available, or the input
+    /// ends.
+    fn fill(&mut self, ahead: usize) {
+        let Some(lexer) = self.lexer.as_mut() else {
+            return;
+        };
+        while self.tokens.len() <= self.position + ahead {
+            match lexer.next() {
+                Some(Ok(token)) => self.tokens.push(token),
+                Some(Err(err)) => self.lex_errors.push(err),
+                None => {
+                    self.lexer = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn next_token(&mut self) -> Option<Token<'a>> {
+        self.fill(0);
         let token = self.tokens.get(self.position);
         self.position += 1;
         token.cloned()
     }
 
-    pub fn peek(&self) -> Option<Token> {
-        let token = self.tokens.get(self.position);
-        token.cloned()
+    pub fn peek(&mut self) -> Option<&Token<'a>> {
+        self.peek_nth(0)
+    }
+
+    /// Looks `n` tokens past the next one without consuming anything.
+    pub fn peek_nth(&mut self, n: usize) -> Option<&Token<'a>> {
+        self.fill(n);
+        self.tokens.get(self.position + n)
+    }
+
+    /// Span of the most recently consumed token, or of the end of input if we ran past it.
+    pub fn last_span(&self) -> Span {
+        match self.tokens.get(self.position.saturating_sub(1)) {
+            Some(token) => token.span,
+            None => self.eof_span(),
+        }
+    }
+
+    /// Empty span directly after the last token.
+    pub fn eof_span(&self) -> Span {
+        match self.tokens.last() {
+            Some(token) => Span::new(
+                token.span.end,
+                token.span.end,
+                token.span.line,
+                token.span.col + token.span.len(),
+            ),
+            None => Span::new(0, 0, 1, 1),
+        }
+    }
+
+    /// Parses every statement, or as much of the file as it can: a statement that fails to parse
+    /// is skipped and left in the `Ast` as [`ast::StmtKind::Error`]. Returns all errors, sorted by
+    /// where they start.
+    pub fn parse_partial(mut self) -> (ast::Ast, Vec<ParseError>) {
+        let prog = self.prog();
+        let mut errors: Vec<_> = self.lex_errors.into_iter().map(ParseError::Lex).collect();
+        errors.extend(self.errors);
+        errors.sort_by_key(|err| err.span().start);
+        let ast = ast::Ast {
+            name: self.name,
+            path: self.path,
+            prog,
+        };
+        (ast, errors)
+    }
+
+    fn prog(&mut self) -> Vec<ast::Stmt> {
+        let mut prog = Vec::new();
+        loop {
+            self.separators();
+            let start = self.position;
+            let Some(stmt) = self.parse_stmt() else {
+                break;
+            };
+            match stmt {
+                Ok(stmt) => prog.push(stmt),
+                Err(err) => {
+                    let mut span = self.recover(err, start);
+                    // Only a stray `}` stops synchronizing before it has skipped anything.
+                    if self.position == start {
+                        span = self.next_token().map_or(span, |token| token.span);
+                    }
+                    self.node(SyntaxKind::Error, start);
+                    prog.push(ast::Stmt::new(ast::StmtKind::Error, span));
+                }
+            }
+        }
+        prog
+    }
+
+    /// Like [`Parser::parse_partial`], but only returns the `Ast` if there were no errors.
+    pub fn parse(self) -> Result<ast::Ast, Vec<ParseError>> {
+        match self.parse_partial() {
+            (ast, errors) if errors.is_empty() => Ok(ast),
+            (_, errors) => Err(errors),
+        }
+    }
+
+    /// Records a syntax node of the tokens from `start` up to the current one. Nodes are recorded
+    /// as they are finished, so of two covering the same tokens the later one is the outer one.
+    fn node(&mut self, kind: SyntaxKind, start: usize) {
+        let end = self.position.min(self.tokens.len());
+        if start < end {
+            self.nodes.push((kind, start..end));
+        }
+    }
+
+    /// Records `err` and skips the rest of the statement that the token at `start` is part of.
+    /// Returns the span of the skipped tokens.
+    ///
+    /// `err` is dropped when the lexer already reported input it left out of the tokens, between
+    /// the start of the statement and where the parser got stuck: the parser only got stuck because
+    /// of it.
+    fn recover(&mut self, err: ParseError, start: usize) -> Span {
+        let from = start
+            .checked_sub(1)
+            .and_then(|last| self.tokens.get(last))
+            .map_or(0, |token| token.span.end);
+        let to = match err {
+            ParseError::UnExpectedEof(_) | ParseError::Unclosed(..) => usize::MAX,
+            _ => err.span().end,
+        };
+        let lexed = self.lex_errors.iter().any(|lex| {
+            let start = lex.span().start;
+            lex.drops_input() && from <= start && start <= to
+        });
+        if !lexed {
+            self.errors.push(err);
+        }
+        self.synchronize(start)
+    }
+
+    /// Moves to the end of the statement from `start` on: the next `;` or line break, or the `}`
+    /// of the enclosing block, that isn't nested in brackets opened along the way. The end itself
+    /// isn't consumed.
+    fn synchronize(&mut self, start: usize) -> Span {
+        self.position = start;
+        let mut depth = 0usize;
+        let mut skipped: Option<Span> = None;
+        while let Some(token) = self.peek() {
+            match token.kind {
+                TokenKind::Newline | TokenKind::Semicolon | TokenKind::RightCurlyBracket
+                    if depth == 0 =>
+                {
+                    break
+                }
+                TokenKind::LeftBrace | TokenKind::LeftBracket | TokenKind::LeftCurlyBracket => {
+                    depth += 1
+                }
+                TokenKind::RightBrace | TokenKind::RightBracket | TokenKind::RightCurlyBracket => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+            let span = token.span;
+            skipped = Some(skipped.map_or(span, |skipped| skipped.to(span)));
+            self.next_token();
+        }
+        skipped.unwrap_or_else(|| {
+            let last = self.last_span();
+            Span::new(last.end, last.end, last.line, last.col + last.len())
+        })
+    }
+
+    /// Collects the `///` lines directly in front of the next statement.
+    fn doc_comment(&mut self) -> Option<String> {
+        let mut lines = Vec::new();
+        while let Some(Token {
+            kind: TokenKind::DocComment,
+            ..
+        }) = self.peek()
+        {
+            lines.push(self.next_token().unwrap().value.into_owned());
+        }
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    /// Skips the newlines and `;`s between statements.
+    fn separators(&mut self) {
+        while let Some(Token {
+            kind: TokenKind::Newline | TokenKind::Semicolon,
+            ..
+        }) = self.peek()
+        {
+            self.next_token();
+        }
     }
 
-    pub fn parse(self) {}
+    /// A statement ends at a line break, a `;`, a closing `}` or the end of the input. Whether a
+    /// line break counts is decided by the lexer, which only emits the ones that can end one.
+    fn terminator(&mut self) -> ParseResultGen<()> {
+        match self.peek() {
+            Some(Token {
+                kind: TokenKind::Newline | TokenKind::Semicolon,
+                ..
+            }) => {
+                self.next_token();
+                Ok(())
+            }
+            Some(Token {
+                kind: TokenKind::RightCurlyBracket,
+                ..
+            })
+            | None => Ok(()),
+            Some(token) => Err(ParseError::UnExpected(
+                "Statement".into(),
+                token.clone().into_owned(),
+            )),
+        }
+    }
 
     pub fn parse_stmt(&mut self) -> Option<ParseResult> {
+        self.separators();
+        let start = self.position;
+        let doc = self.doc_comment();
         let token = self.peek()?;
-        match token.kind {
+        let kind = match token.kind {
+            TokenKind::Let => SyntaxKind::LetStmt,
+            TokenKind::For | TokenKind::While | TokenKind::Break | TokenKind::Continue => {
+                SyntaxKind::ControlStmt
+            }
+            _ => SyntaxKind::ExprStmt,
+        };
+        let stmt = match token.kind {
             token::TokenKind::String
+            | token::TokenKind::InterpStart
             | token::TokenKind::Int
             | token::TokenKind::Float
-            | token::TokenKind::Ident => {
-                let expr = match expr(self) {
-                    Ok(expr) => expr,
-                    Err(err) => return Some(Err(err)),
+            | token::TokenKind::True
+            | token::TokenKind::False
+            | token::TokenKind::Ident
+            | token::TokenKind::LeftBrace
+            | token::TokenKind::LeftBracket
+            | token::TokenKind::LeftCurlyBracket
+            | token::TokenKind::Pipe
+            | token::TokenKind::Match
+            | token::TokenKind::Op(Operator::Sub | Operator::Not) => expr_stmt(self),
+            token::TokenKind::Let => self.parse_let().map(|stmt| stmt.with_doc(doc)),
+            token::TokenKind::If => self.parse_if(),
+            token::TokenKind::For => parse_for(self),
+            token::TokenKind::While => parse_while(self),
+            token::TokenKind::Break | token::TokenKind::Continue => {
+                let token = self.next_token().unwrap();
+                let kind = match token.kind {
+                    TokenKind::Break => ast::StmtKind::Break,
+                    _ => ast::StmtKind::Continue,
                 };
-                Some(Ok(ast::Stmt::Expr(expr)))
-            }
-            token::TokenKind::Let => Some(self.parse_let()),
-            token::TokenKind::If => Some(self.parse_if()),
-            token::TokenKind::Else => todo!(),
-            token::TokenKind::For => todo!(),
-            token::TokenKind::While => todo!(),
-            token::TokenKind::Match => todo!(),
-            token::TokenKind::Comma => todo!(),
-            _ => todo!(
-                "We really shouldn't be handling this every, but I will do some more thinking."
-            ),
+                Ok(ast::Stmt::new(kind, token.span))
+            }
+            _ => Err(ParseError::UnExpected(
+                "Statement".into(),
+                token.clone().into_owned(),
+            )),
+        };
+        if stmt.is_ok() {
+            self.node(kind, start);
         }
+        Some(stmt.and_then(|stmt| self.terminator().map(|_| stmt)))
     }
 
+    /// Backtracks on failure, also dropping the errors recovered from on the way.
     pub fn try_parse(&mut self, try_parse: ParseFn) -> ParseResult {
-        let position = self.position;
-        match try_parse(self) {
-            Ok(stmt) => Ok(stmt),
-            Err(err) => {
-                self.position = position;
-                Err(err)
-            }
-        }
+        self.try_parse_gen(try_parse)
     }
 
     pub fn try_parse_gen<T>(&mut self, try_parse: ParseFnGen<T>) -> ParseResultGen<T> {
         let position = self.position;
+        let errors = self.errors.len();
+        let nodes = self.nodes.len();
         match try_parse(self) {
             Ok(stmt) => Ok(stmt),
             Err(err) => {
                 self.position = position;
+                self.errors.truncate(errors);
+                self.nodes.truncate(nodes);
                 Err(err)
             }
         }
@@ -309,10 +1757,810 @@ impl Parser {
         parse_if(self)
     }
 
+    /// If both fail, the error that got further into the input is the more telling one, and the
+    /// first one on a tie.
     pub fn parse_or_try(&mut self, try_parse: ParseFn, or_try: ParseFn) -> ParseResult {
         match self.try_parse(try_parse) {
             Ok(stmt) => Ok(stmt),
-            Err(_) => self.try_parse(or_try),
+            Err(first) => match self.try_parse(or_try) {
+                Err(second) if first.span().start >= second.span().start => Err(first),
+                result => result,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        lexer::Lexer,
+        token::{Operator, TokenKind},
+        ParseError, Parser,
+    };
+    use ast::{
+        Expr, ExprKind, Mutability, Pattern, PatternKind, RangeLimits, Span, Stmt, StmtKind, Type,
+        ValueKind,
+    };
+
+    fn parser(input: &str) -> Parser<'_> {
+        Parser::from_lexer(Lexer::new(input))
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let mut parser = parser("/// The answer.\n/// Always.\nlet x = 42 // not docs\n");
+        let stmt = parser.parse_stmt().unwrap().ok().unwrap();
+        assert_eq!(stmt.doc.as_deref(), Some("The answer.\nAlways."));
+        let StmtKind::Let(name, _, _, expr) = stmt.kind else {
+            panic!("expected a let statement");
+        };
+        assert_eq!(name, "x");
+        assert!(matches!(
+            expr.kind,
+            ast::ExprKind::Value(ast::Value {
+                kind: ValueKind::Int(42),
+                ..
+            })
+        ));
+        assert!(parser.parse_stmt().is_none());
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let mut parser = parser("\"hello {name}, you are {age + 1}\"");
+        let stmt = parser.parse_stmt().unwrap().ok().unwrap();
+        let StmtKind::Expr(ast::Expr {
+            kind: ast::ExprKind::Interpolation(parts),
+            span,
+        }) = stmt.kind
+        else {
+            panic!("expected an interpolation");
+        };
+        assert_eq!(span, ast::Span::new(0, 33, 1, 1));
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0], ast::StringPart::Literal("hello ".into()));
+        assert!(matches!(
+            &parts[1],
+            ast::StringPart::Expr(ast::Expr {
+                kind: ast::ExprKind::Value(ast::Value {
+                    kind: ValueKind::Ident(name),
+                    ..
+                }),
+                ..
+            }) if name == "name"
+        ));
+        assert_eq!(parts[2], ast::StringPart::Literal(", you are ".into()));
+        assert!(matches!(
+            &parts[3],
+            ast::StringPart::Expr(ast::Expr {
+                kind: ast::ExprKind::Binary(_, ast::Operation::Add, _),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_number_literals() {
+        let err = parser("0xFF + 1_000 + 1.5e-3 + 99999999999999999999")
+            .parse_stmt()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::OutOfRange("99999999999999999999".into(), Span::new(24, 44, 1, 25))
+        );
+
+        let stmt = parser("0b1010u8").parse_stmt().unwrap().ok().unwrap();
+        assert!(matches!(
+            stmt.kind,
+            StmtKind::Expr(ast::Expr {
+                kind: ast::ExprKind::Value(ast::Value {
+                    kind: ValueKind::Int(10),
+                    ..
+                }),
+                ..
+            })
+        ));
+
+        // The `-` counts towards the range, in expressions and in patterns.
+        let ast = parser("let a = -128i8\nmatch a { -128i8 => 1, _ => 0 }")
+            .parse()
+            .unwrap();
+        let StmtKind::Let(_, _, _, value) = &ast.prog[0].kind else {
+            panic!("expected a let statement");
+        };
+        assert_eq!(
+            value.kind,
+            ast::ExprKind::Value(ast::Value::new(
+                ValueKind::Int(-128),
+                Span::new(8, 14, 1, 9)
+            ))
+        );
+        let err = parser("-129i8").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::OutOfRange("-129i8".into(), Span::new(0, 6, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_bool_literals() {
+        let ast = parser("let t = true\nmatch t { false => 0, true | _ => 1 }")
+            .parse()
+            .unwrap();
+        let StmtKind::Let(_, _, _, value) = &ast.prog[0].kind else {
+            panic!("expected a let statement");
+        };
+        assert_eq!(
+            value.kind,
+            ast::ExprKind::Value(ast::Value::new(
+                ValueKind::Bool(true),
+                Span::new(8, 12, 1, 9)
+            ))
+        );
+        let StmtKind::Expr(ast::Expr {
+            kind: ast::ExprKind::Match(_, cases),
+            ..
+        }) = &ast.prog[1].kind
+        else {
+            panic!("expected a match");
+        };
+        assert_eq!(
+            cases[0].0.kind,
+            ast::PatternKind::Literal(ast::Value::new(
+                ValueKind::Bool(false),
+                Span::new(23, 28, 2, 11)
+            ))
+        );
+
+        // It is a keyword, not a name.
+        assert!(parser("let true = 1").parse().is_err());
+    }
+
+    #[test]
+    fn test_statement_termination() {
+        let mut stmts = parser("let a = 1 +\n  2; let b = a\n\n a * b\n");
+        let spans: Vec<_> = std::iter::from_fn(|| stmts.parse_stmt())
+            .map(|stmt| stmt.ok().unwrap().span)
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                Span::new(0, 15, 1, 1),
+                Span::new(17, 26, 2, 6),
+                Span::new(29, 34, 4, 2),
+            ]
+        );
+
+        let err = parser("let c = 1 2").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(err.span(), Span::new(10, 11, 1, 11));
+    }
+
+    #[test]
+    fn test_type_annotations() {
+        let typed = |input: &str| match parser(input).parse_stmt().unwrap().unwrap().kind {
+            StmtKind::Let(_, _, typed, _) => typed,
+            kind => panic!("expected a let, got {kind:?}"),
+        };
+        assert_eq!(typed("let x: int = 10"), Some(Type::Int));
+        assert_eq!(typed("let x = 10"), None);
+        assert_eq!(
+            typed("let x: [string] = y"),
+            Some(Type::List(Box::new(Type::String)))
+        );
+        assert_eq!(
+            typed("let x: (bool, (float,), ()) = y"),
+            Some(Type::Tuple(vec![
+                Type::Bool,
+                Type::Tuple(vec![Type::Float]),
+                Type::Tuple(vec![])
+            ]))
+        );
+        assert_eq!(
+            typed("let add: (int, int) -> (int) -> int = f"),
+            Some(Type::Func(
+                vec![Type::Int, Type::Int],
+                Box::new(Type::Func(vec![Type::Int], Box::new(Type::Int)))
+            ))
+        );
+
+        let func = parser("let add(a: int, b): int = a + b")
+            .parse_stmt()
+            .unwrap()
+            .unwrap();
+        let StmtKind::Func(_, params, ret, _) = func.kind else {
+            panic!("expected a function");
+        };
+        assert_eq!(
+            params,
+            vec![("a".into(), Some(Type::Int)), ("b".into(), None)]
+        );
+        assert_eq!(ret, Some(Type::Int));
+
+        let err = parser("let x: [int = 1").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(err.span(), Span::new(12, 13, 1, 13));
+    }
+
+    /// Writes `expr` out with every operation in parentheses.
+    fn sexpr(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Value(value) => match &value.kind {
+                ValueKind::Int(int) => int.to_string(),
+                ValueKind::Ident(name) => name.to_string(),
+                kind => format!("{kind:?}"),
+            },
+            ExprKind::Binary(lhs, op, rhs) => format!("({op:?} {} {})", sexpr(lhs), sexpr(rhs)),
+            ExprKind::Unary(op, expr) => format!("({op:?} {})", sexpr(expr)),
+            ExprKind::Call(callee, args) => {
+                let args: Vec<_> = args.iter().map(sexpr).collect();
+                format!("{}({})", sexpr(callee), args.join(", "))
+            }
+            kind => format!("{kind:?}"),
+        }
+    }
+
+    #[test]
+    fn test_expressions() {
+        let parse = |input: &str| match parser(input).parse_stmt().unwrap() {
+            Ok(Stmt {
+                kind: StmtKind::Expr(expr),
+                ..
+            }) => sexpr(&expr),
+            stmt => panic!("expected an expression, got {stmt:?}"),
+        };
+        assert_eq!(parse("a - b - c"), "(Sub (Sub a b) c)");
+        assert_eq!(parse("a / b * c"), "(Mul (Div a b) c)");
+        assert_eq!(parse("1 + 2 * 3 == 7"), "(EqEq (Add 1 (Mul 2 3)) 7)");
+        assert_eq!(parse("(a + b) * c"), "(Mul (Add a b) c)");
+        assert_eq!(parse("-x * -(y - 1)"), "(Mul (Neg x) (Neg (Sub y 1)))");
+        assert_eq!(parse("!a == b"), "(EqEq (Not a) b)");
+        assert_eq!(
+            parse("f(x) + g(1, y * 2) * 3"),
+            "(Add f(x) (Mul g(1, (Mul y 2)) 3))"
+        );
+        assert_eq!(parse("--1"), "(Neg -1)");
+        assert_eq!(parse("-9223372036854775808"), "-9223372036854775808");
+        assert_eq!(parse("-2 * 3"), "(Mul -2 3)");
+
+        let expr = parser("(a +\n b) * c").parse_stmt().unwrap().unwrap();
+        assert_eq!(expr.span, Span::new(1, 12, 1, 2));
+
+        let err = parser("(a + b * c").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Unclosed(TokenKind::LeftBrace, Span::new(0, 1, 1, 1))
+        );
+        let err = parser("a + * b").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(err.span(), Span::new(4, 5, 1, 5));
+    }
+
+    #[test]
+    fn test_function_calls() {
+        let parse = |input: &str| match parser(input).parse_stmt().unwrap() {
+            Ok(Stmt {
+                kind: StmtKind::Expr(expr),
+                ..
+            }) => sexpr(&expr),
+            stmt => panic!("expected an expression, got {stmt:?}"),
+        };
+        assert_eq!(
+            parse("print_a_then_b(\"a\", \"b\")"),
+            "print_a_then_b(String(\"a\"), String(\"b\"))"
+        );
+        assert_eq!(parse("f()"), "f()");
+        assert_eq!(parse("f(g(x), h(1 + 2,),)"), "f(g(x), h((Add 1 2)))");
+        assert_eq!(parse("make_adder(1)(2)"), "make_adder(1)(2)");
+        assert_eq!(parse("(f)(x, \n y)"), "f(x, y)");
+
+        let expr = parser("make_adder(1)(2)").parse_stmt().unwrap().unwrap();
+        assert_eq!(expr.span, Span::new(0, 16, 1, 1));
+
+        let err = parser("f(1, g(2)").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Unclosed(TokenKind::LeftBrace, Span::new(1, 2, 1, 2))
+        );
+        let err = parser("f(1 2)").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(err.span(), Span::new(4, 5, 1, 5));
+        let err = parser("f(,)").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(err.span(), Span::new(2, 3, 1, 3));
+    }
+
+    #[test]
+    fn test_if() {
+        let stmt = parser("if a < 1 { b } else if a < 2 {\n c\n d\n} else {}")
+            .parse_stmt()
+            .unwrap()
+            .unwrap();
+        assert_eq!(stmt.span, Span::new(0, 46, 1, 1));
+        let StmtKind::Expr(Expr {
+            kind: ExprKind::If(cond, then, Some(otherwise)),
+            ..
+        }) = stmt.kind
+        else {
+            panic!("expected an if with an else");
+        };
+        assert_eq!(sexpr(&cond), "(Less a 1)");
+        assert!(matches!(then.kind, ExprKind::Block(stmts) if stmts.len() == 1));
+        let ExprKind::If(_, then, Some(otherwise)) = otherwise.kind else {
+            panic!("expected an else if");
+        };
+        assert!(matches!(then.kind, ExprKind::Block(stmts) if stmts.len() == 2));
+        assert_eq!(otherwise.kind, ExprKind::Block(vec![]));
+
+        let stmt = parser("let x = 1 + if a { 1 } else { 2 }")
+            .parse_stmt()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            stmt.kind,
+            StmtKind::Let(
+                _,
+                _,
+                _,
+                Expr {
+                    kind: ExprKind::Binary(..),
+                    ..
+                }
+            )
+        ));
+        let stmt = parser("if a { 1 }").parse_stmt().unwrap().unwrap();
+        assert!(matches!(
+            stmt.kind,
+            StmtKind::Expr(Expr {
+                kind: ExprKind::If(_, _, None),
+                ..
+            })
+        ));
+
+        let err = parser("if a { 1").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Unclosed(TokenKind::LeftCurlyBracket, Span::new(5, 6, 1, 6))
+        );
+        let err = parser("if a { 1 } else 2")
+            .parse_stmt()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.span(), Span::new(16, 17, 1, 17));
+
+        let ast = parser("if c { 1 }\nelse { 2 }\nif c { 3 }\n4")
+            .parse()
+            .unwrap();
+        assert_eq!(ast.prog.len(), 3);
+        assert!(matches!(
+            ast.prog[0].kind,
+            StmtKind::Expr(Expr {
+                kind: ExprKind::If(_, _, Some(_)),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_while() {
+        for input in ["while(x < 10) {\n x\n}", "while x < 10 { x }"] {
+            let stmt = parser(input).parse_stmt().unwrap().unwrap();
+            assert_eq!(stmt.span.len(), input.len());
+            let StmtKind::While(cond, body) = stmt.kind else {
+                panic!("expected a while loop");
+            };
+            assert_eq!(sexpr(&cond), "(Less x 10)");
+            assert!(matches!(
+                body.kind,
+                StmtKind::Expr(Expr {
+                    kind: ExprKind::Block(..),
+                    ..
+                })
+            ));
+        }
+
+        let ast = parser("while a { if b { break }\n continue }")
+            .parse()
+            .unwrap();
+        let StmtKind::While(_, body) = &ast.prog[0].kind else {
+            panic!("expected a while loop");
+        };
+        let StmtKind::Expr(Expr {
+            kind: ExprKind::Block(stmts),
+            ..
+        }) = &body.kind
+        else {
+            panic!("expected a block");
+        };
+        assert_eq!(stmts[1].kind, StmtKind::Continue);
+        assert_eq!(stmts[1].span, Span::new(26, 34, 2, 2));
+
+        let err = parser("while x < 10").parse_stmt().unwrap().unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::Expected(_, TokenKind::LeftCurlyBracket, _)
+        ));
+    }
+
+    #[test]
+    fn test_for() {
+        let stmt = parser("for (x <= 0 ..= 10) {\n print(x)\n}")
+            .parse_stmt()
+            .unwrap()
+            .unwrap();
+        assert_eq!(stmt.span, Span::new(0, 33, 1, 1));
+        let StmtKind::For(name, iter, _) = stmt.kind else {
+            panic!("expected a for loop");
+        };
+        assert_eq!(name, "x");
+        assert_eq!(
+            iter.kind,
+            ExprKind::Range(
+                Box::new(ast::Value::new(ValueKind::Int(0), Span::new(10, 11, 1, 11)).into()),
+                Box::new(ast::Value::new(ValueKind::Int(10), Span::new(16, 18, 1, 17)).into()),
+                RangeLimits::Inclusive,
+                None
+            )
+        );
+
+        let range = |input: &str| match parser(input).parse_stmt().unwrap().unwrap().kind {
+            StmtKind::For(_, iter, _) => iter,
+            kind => panic!("expected a for loop, got {kind:?}"),
+        };
+        let iter = range("for i <= n - 1 .. 0 step 2 * k { }");
+        let ExprKind::Range(start, end, RangeLimits::Exclusive, Some(step)) = iter.kind else {
+            panic!("expected an exclusive range with a step");
+        };
+        assert_eq!(
+            (sexpr(&start), sexpr(&end), sexpr(&step)),
+            ("(Sub n 1)".into(), "0".into(), "(Mul 2 k)".into())
+        );
+        assert_eq!(iter.span, Span::new(9, 30, 1, 10));
+        let iter = range("for c <= [1, 2,] { }");
+        assert!(matches!(iter.kind, ExprKind::List(elems) if elems.len() == 2));
+
+        let err = parser("for (x <= xs { }")
+            .parse_stmt()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.span(), Span::new(13, 14, 1, 14));
+        let err = parser("for x in xs { }").parse_stmt().unwrap().unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::Expected(_, TokenKind::Op(Operator::LessEq), _)
+        ));
+    }
+
+    #[test]
+    fn test_match() {
+        let input = "match (x, 2) {\n  (0, _) | (_, 0) => 0\n  (n, -1 ..= 1) if n > 0 => { n }, [first, ..] => first\n  Point { x, y: 0 } => x\n}";
+        let expr = parser(input).parse_stmt().unwrap().unwrap();
+        assert_eq!(expr.span, Span::new(0, input.len(), 1, 1));
+        let StmtKind::Expr(Expr {
+            kind: ExprKind::Match(scrutinee, cases),
+            ..
+        }) = expr.kind
+        else {
+            panic!("expected a match expression");
+        };
+        assert!(matches!(scrutinee.kind, ExprKind::Tuple(elems) if elems.len() == 2));
+        assert_eq!(cases.len(), 4);
+
+        let PatternKind::Or(alternatives) = &cases[0].0.kind else {
+            panic!("expected alternatives");
+        };
+        assert_eq!(alternatives.len(), 2);
+        assert_eq!(cases[0].0.span, Span::new(17, 32, 2, 3));
+        assert!(matches!(
+            &alternatives[0].kind,
+            PatternKind::Tuple(elems) if matches!(
+                (&elems[0].kind, &elems[1].kind),
+                (PatternKind::Literal(_), PatternKind::Wildcard)
+            )
+        ));
+
+        let (pattern, guard, body) = &cases[1];
+        let PatternKind::Tuple(elems) = &pattern.kind else {
+            panic!("expected a tuple pattern");
+        };
+        assert!(matches!(
+            &elems[1].kind,
+            PatternKind::Range(start, end, RangeLimits::Inclusive)
+                if start.kind == ValueKind::Int(-1) && end.kind == ValueKind::Int(1)
+        ));
+        assert_eq!(sexpr(guard.as_ref().unwrap()), "(More n 0)");
+        assert!(matches!(body.kind, ExprKind::Block(_)));
+
+        assert!(matches!(
+            &cases[2].0.kind,
+            PatternKind::List(elems) if elems[1].kind == PatternKind::Rest
+        ));
+        let PatternKind::Struct(name, fields) = &cases[3].0.kind else {
+            panic!("expected a struct pattern");
+        };
+        assert_eq!(*name, "Point");
+        assert_eq!(
+            fields[0],
+            (
+                "x".into(),
+                Pattern::new(PatternKind::Binding("x".into()), Span::new(104, 105, 4, 11))
+            )
+        );
+        assert!(matches!(fields[1].1.kind, PatternKind::Literal(_)));
+
+        let tuple = |input: &str| match parser(input).parse_stmt().unwrap().unwrap().kind {
+            StmtKind::Expr(expr) => expr.kind,
+            kind => panic!("expected an expression, got {kind:?}"),
+        };
+        assert!(matches!(tuple("()"), ExprKind::Tuple(elems) if elems.is_empty()));
+        assert!(matches!(tuple("(1,)"), ExprKind::Tuple(elems) if elems.len() == 1));
+        assert!(matches!(tuple("(1)"), ExprKind::Value(_)));
+
+        let err = parser("match x { 1 2 }").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Expected(
+                "Match".into(),
+                TokenKind::FatArrow,
+                Span::new(12, 13, 1, 13)
+            )
+        );
+        let err = parser("match x { _ => 1")
+            .parse_stmt()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Unclosed(TokenKind::LeftCurlyBracket, Span::new(8, 9, 1, 9))
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let source = "let print_a_then_b(a, b) = {
+    print(a)
+    print(b)
+} 
+
+print_a_then_b(\"a\", \"b\")";
+        let ast = parser(source).parse().unwrap();
+        assert_eq!(ast.prog.len(), 2);
+        let StmtKind::Func(_, params, _, body) = &ast.prog[0].kind else {
+            panic!("expected a function");
+        };
+        assert_eq!(params.len(), 2);
+        assert!(matches!(&body.kind, ExprKind::Block(stmts) if stmts.len() == 2));
+        assert_eq!(body.span, Span::new(27, 56, 1, 28));
+
+        let stmt = parser("let x = { let y = 2; { y } } * 3")
+            .parse_stmt()
+            .unwrap()
+            .unwrap();
+        let StmtKind::Let(_, _, _, value) = stmt.kind else {
+            panic!("expected a let statement");
+        };
+        let ExprKind::Binary(block, ast::Operation::Mul, _) = value.kind else {
+            panic!("expected a multiplication");
+        };
+        assert!(matches!(
+            &block.kind,
+            ExprKind::Block(stmts) if matches!(
+                &stmts[1].kind,
+                StmtKind::Expr(Expr { kind: ExprKind::Block(_), .. })
+            )
+        ));
+
+        let err = parser("{ 1").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Unclosed(TokenKind::LeftCurlyBracket, Span::new(0, 1, 1, 1))
+        );
+
+        // Blocks inside brackets still take one statement per line.
+        for source in [
+            "apply(|x| {\n  let y = x * 2\n  y + 1\n}, 3)",
+            "[{\n 1\n 2\n}]",
+            "(match 1 {\n 1 => 2\n _ => 3\n})",
+        ] {
+            let ast = parser(source).parse().unwrap();
+            assert_eq!(ast.prog.len(), 1, "{source}");
+        }
+    }
+
+    #[test]
+    fn test_lambdas() {
+        let parse = |input: &str| match parser(input).parse_stmt().unwrap() {
+            Ok(Stmt {
+                kind: StmtKind::Expr(expr),
+                ..
+            }) => expr,
+            stmt => panic!("expected an expression, got {stmt:?}"),
+        };
+        let expr = parse("|a, b: int| a + b");
+        assert_eq!(expr.span, Span::new(0, 17, 1, 1));
+        let ExprKind::Lambda(params, body) = expr.kind else {
+            panic!("expected a lambda");
+        };
+        assert_eq!(params, [("a".into(), None), ("b".into(), Some(Type::Int))]);
+        assert_eq!(sexpr(&body), "(Add a b)");
+
+        assert!(matches!(
+            parse("|| { 1 }").kind,
+            ExprKind::Lambda(params, _) if params.is_empty()
+        ));
+        let ExprKind::Call(_, args) = parse("map(|x| x * 2, xs)").kind else {
+            panic!("expected a call");
+        };
+        assert!(matches!(
+            args[..],
+            [
+                Expr {
+                    kind: ExprKind::Lambda(..),
+                    ..
+                },
+                _
+            ]
+        ));
+
+        let err = parser("|a b| a").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Expected("Lambda".into(), TokenKind::Pipe, Span::new(3, 4, 1, 4))
+        );
+        let err = parser("|a,").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Unclosed(TokenKind::Pipe, Span::new(0, 1, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_assignment() {
+        let stmt = parser("let mut x = 1").parse_stmt().unwrap().unwrap();
+        assert!(matches!(
+            stmt.kind,
+            StmtKind::Let(_, Mutability::Mut, None, _)
+        ));
+
+        let stmt = parser("total += f(x) * 2").parse_stmt().unwrap().unwrap();
+        assert_eq!(stmt.span, Span::new(0, 17, 1, 1));
+        let StmtKind::Assign(target, op, value) = stmt.kind else {
+            panic!("expected an assignment");
+        };
+        assert_eq!(
+            (sexpr(&target), op, sexpr(&value)),
+            (
+                "total".into(),
+                Some(ast::Operation::Add),
+                "(Mul f(x) 2)".into()
+            )
+        );
+        let stmt = parser("x =\n  1").parse_stmt().unwrap().unwrap();
+        assert!(matches!(stmt.kind, StmtKind::Assign(_, None, _)));
+
+        let err = parser("f(x) = 1").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Expected("Assignment".into(), TokenKind::Ident, Span::new(0, 4, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_recovery() {
+        let source =
+            "let a = 1 2\nlet b = )\nwhile a < 2 {\n  let = 3\n  print(a)\n}\n}\nprint(1, 2 3)\nb";
+        let (ast, errors) = parser(source).parse_partial();
+        let kinds: Vec<_> = ast.prog.iter().map(|stmt| &stmt.kind).collect();
+        assert!(matches!(
+            kinds[..],
+            [
+                StmtKind::Error,
+                StmtKind::Let(
+                    _,
+                    _,
+                    _,
+                    Expr {
+                        kind: ExprKind::Error,
+                        ..
+                    }
+                ),
+                StmtKind::While(..),
+                StmtKind::Error,
+                StmtKind::Error,
+                StmtKind::Expr(_),
+            ]
+        ));
+        assert_eq!(ast.prog[0].span, Span::new(0, 11, 1, 1));
+        assert_eq!(ast.prog[3].span, Span::new(59, 60, 7, 1));
+        let StmtKind::While(_, body) = &ast.prog[2].kind else {
+            unreachable!()
+        };
+        assert!(matches!(
+            &body.kind,
+            StmtKind::Expr(Expr { kind: ExprKind::Block(stmts), .. })
+                if stmts[0].kind == StmtKind::Error && matches!(stmts[1].kind, StmtKind::Expr(_))
+        ));
+
+        let spans: Vec<_> = errors.iter().map(|err| err.span().start).collect();
+        assert_eq!(spans, [10, 20, 42, 59, 72]);
+        assert_eq!(
+            errors[2],
+            ParseError::Expected("Variable".into(), TokenKind::Ident, Span::new(42, 43, 4, 7))
+        );
+        assert!(parser(source).parse().is_err());
+
+        // Whatever it is fed, the parser gives up gracefully.
+        let program = "/// Doc\nlet f(x: (int, [float]) -> int) = match x { (0, _) | [1 ..= 2, ..] if x > 0 => { f(x - 1) }, _ => \"{x}\" }\nfor i <= 0 .. 10 step 2 { while !i { break } }\n";
+        for (end, _) in program.char_indices() {
+            parser(&program[..end]).parse_partial();
+        }
+        for garbage in [
+            "}}}", ")(", "let let", "match {", "=> |", "for for", "(,)", "[..]",
+        ] {
+            parser(garbage).parse_partial();
+        }
+    }
+
+    #[test]
+    fn test_lex_errors() {
+        // The parser doesn't trip over what the lexer already reported.
+        for source in [
+            "let x = 1 % 2",
+            "let x = (1 € 2)\nx",
+            "let s = \"abc",
+            "let s = \"{\"",
+            "let f(a, b) = { a /* b }",
+            "let x = [1, 2 @]",
+        ] {
+            let errors = parser(source).parse().unwrap_err();
+            assert!(
+                matches!(errors[..], [ParseError::Lex(_)]),
+                "{source}: {errors:?}"
+            );
         }
     }
+
+    #[test]
+    fn test_diagnostics() {
+        let source = "let f(a b) = a\nlet s = \"\\q\" + (1\n";
+        let errors = parser(source).parse().unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "expected `,` in parameter list",
+                "invalid escape `\\q`",
+                "unclosed `(`"
+            ]
+        );
+        assert_eq!(
+            errors[2].diagnostic().render(source, "main.monk", false),
+            "error: unclosed `(`
+ --> main.monk:2:16
+  |
+2 | let s = \"\\q\" + (1
+  |                ^ opened here, but never closed
+  = help: add the matching `)`
+"
+        );
+
+        let err = parser("256u8").parse_stmt().unwrap().unwrap_err();
+        assert_eq!(
+            err.diagnostic().notes,
+            ["`u8` ints go from 0 to 255".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let ast = parser("let a = 1\na + 2\n")
+            .with_path("src/main.monk")
+            .parse()
+            .unwrap();
+        assert_eq!(ast.name.as_deref(), Some("main"));
+        assert_eq!(ast.prog.len(), 2);
+
+        let errors = parser("let a 1\nlet b = 2 3; a\n) € 4")
+            .parse()
+            .unwrap_err();
+        let spans: Vec<_> = errors.iter().map(|err| err.span().start).collect();
+        assert_eq!(spans, vec![6, 18, 23, 25]);
+        assert!(matches!(errors[3], ParseError::Lex(_)));
+    }
 }
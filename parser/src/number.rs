@@ -0,0 +1,232 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Suffix {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+}
+
+const SUFFIXES: [(&str, Suffix); 10] = [
+    ("i8", Suffix::I8),
+    ("i16", Suffix::I16),
+    ("i32", Suffix::I32),
+    ("i64", Suffix::I64),
+    ("u8", Suffix::U8),
+    ("u16", Suffix::U16),
+    ("u32", Suffix::U32),
+    ("u64", Suffix::U64),
+    ("f32", Suffix::F32),
+    ("f64", Suffix::F64),
+];
+
+impl Suffix {
+    pub fn is_float(self) -> bool {
+        matches!(self, Suffix::F32 | Suffix::F64)
+    }
+
+    pub fn name(self) -> &'static str {
+        SUFFIXES
+            .iter()
+            .find(|(_, suffix)| *suffix == self)
+            .map_or("", |(name, _)| name)
+    }
+
+    fn range(self) -> (i128, i128) {
+        match self {
+            Suffix::I8 => (i8::MIN.into(), i8::MAX.into()),
+            Suffix::I16 => (i16::MIN.into(), i16::MAX.into()),
+            Suffix::I32 => (i32::MIN.into(), i32::MAX.into()),
+            Suffix::I64 | Suffix::F32 | Suffix::F64 => (i64::MIN.into(), i64::MAX.into()),
+            Suffix::U8 => (0, u8::MAX.into()),
+            Suffix::U16 => (0, u16::MAX.into()),
+            Suffix::U32 => (0, u32::MAX.into()),
+            // Every int ends up as an `i64` in the AST, so `u64` can't go past `i64::MAX`.
+            Suffix::U64 => (0, i64::MAX.into()),
+        }
+    }
+}
+
+/// A syntactically valid numeric literal, split into its radix, digits and suffix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Number {
+    pub radix: u32,
+    pub digits: String,
+    pub float: bool,
+    pub suffix: Option<Suffix>,
+}
+
+impl Number {
+    pub fn parse(text: &str) -> Option<Number> {
+        let (radix, body) = match text.get(..2) {
+            Some("0x" | "0X") => (16, &text[2..]),
+            Some("0o" | "0O") => (8, &text[2..]),
+            Some("0b" | "0B") => (2, &text[2..]),
+            _ => (10, text),
+        };
+
+        let (body, suffix) = SUFFIXES
+            .iter()
+            .filter(|(_, kind)| radix == 10 || !kind.is_float())
+            .find_map(|(suffix, kind)| {
+                let body = body.strip_suffix(suffix)?;
+                (!body.is_empty()).then_some((body, Some(*kind)))
+            })
+            .unwrap_or((body, None));
+
+        let digits: String = body.chars().filter(|c| *c != '_').collect();
+        if digits.is_empty() {
+            return None;
+        }
+
+        let float = match radix {
+            10 => {
+                let float = decimal(&digits)?;
+                if float && suffix.is_some_and(|suffix| !suffix.is_float()) {
+                    return None;
+                }
+                float || suffix.is_some_and(Suffix::is_float)
+            }
+            radix => {
+                if !digits.chars().all(|c| c.is_digit(radix)) {
+                    return None;
+                }
+                false
+            }
+        };
+
+        Some(Number {
+            radix,
+            digits,
+            float,
+            suffix,
+        })
+    }
+
+    /// The value of an int literal, or `None` if it doesn't fit its type.
+    pub fn int(&self) -> Option<i64> {
+        let value = i128::from_str_radix(&self.digits, self.radix).ok()?;
+        self.fit(value)
+    }
+
+    /// The value of an int literal with a `-` in front, which can go one further than a positive
+    /// one, as in `-128i8`.
+    pub fn negative_int(&self) -> Option<i64> {
+        let value = i128::from_str_radix(&self.digits, self.radix).ok()?;
+        self.fit(-value)
+    }
+
+    fn fit(&self, value: i128) -> Option<i64> {
+        let (min, max) = self.suffix.unwrap_or(Suffix::I64).range();
+        if value < min || value > max {
+            return None;
+        }
+        i64::try_from(value).ok()
+    }
+
+    /// What values a literal of this type can have, for when it doesn't fit.
+    pub fn range_note(&self) -> String {
+        match self.suffix {
+            Some(Suffix::F32) => "`f32` floats go up to about 3.4e38".into(),
+            _ if self.float => "floats are 64 bit, up to about 1.8e308".into(),
+            Some(Suffix::U64) => {
+                "`u64` ints are kept in 64 bit signed ints, so they go from 0 to 9223372036854775807"
+                    .into()
+            }
+            Some(suffix) => {
+                let (min, max) = suffix.range();
+                format!("`{}` ints go from {min} to {max}", suffix.name())
+            }
+            None => "ints are 64 bit, from -9223372036854775808 to 9223372036854775807".into(),
+        }
+    }
+
+    /// The value of a float literal, or `None` if it overflows its type.
+    pub fn float(&self) -> Option<f64> {
+        let value = self.digits.parse::<f64>().ok()?;
+        let finite = match self.suffix {
+            Some(Suffix::F32) => (value as f32).is_finite(),
+            _ => value.is_finite(),
+        };
+        finite.then_some(value)
+    }
+}
+
+/// Checks `digits ['.' digits] [('e' | 'E') ['+' | '-'] digits]` and reports whether the literal
+/// has a fraction or an exponent.
+fn decimal(digits: &str) -> Option<bool> {
+    let (mantissa, exponent) = match digits.find(['e', 'E']) {
+        Some(index) => (&digits[..index], Some(&digits[index + 1..])),
+        None => (digits, None),
+    };
+    let (int, fraction) = match mantissa.split_once('.') {
+        Some((int, fraction)) => (int, Some(fraction)),
+        None => (mantissa, None),
+    };
+
+    let is_digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    if !is_digits(int) || fraction.is_some_and(|fraction| !is_digits(fraction)) {
+        return None;
+    }
+    if let Some(exponent) = exponent {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        if !is_digits(exponent) {
+            return None;
+        }
+    }
+    Some(fraction.is_some() || exponent.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::number::{Number, Suffix};
+
+    #[test]
+    fn test_number() {
+        let int = |text: &str| Number::parse(text).and_then(|number| number.int());
+        assert_eq!(int("0xFF"), Some(255));
+        assert_eq!(int("0o17"), Some(15));
+        assert_eq!(int("0b1010"), Some(10));
+        assert_eq!(int("1_000_000"), Some(1_000_000));
+        assert_eq!(int("255u8"), Some(255));
+        assert_eq!(int("256u8"), None);
+        assert_eq!(int("99999999999999999999"), None);
+        assert_eq!(int("9223372036854775807"), Some(i64::MAX));
+
+        let negative = |text: &str| Number::parse(text).and_then(|number| number.negative_int());
+        assert_eq!(negative("9223372036854775808"), Some(i64::MIN));
+        assert_eq!(negative("9223372036854775809"), None);
+        assert_eq!(negative("128i8"), Some(-128));
+        assert_eq!(negative("129i8"), None);
+        assert_eq!(negative("1u8"), None);
+        assert_eq!(negative("0u8"), Some(0));
+
+        let float = |text: &str| Number::parse(text).and_then(|number| number.float());
+        assert_eq!(float("1.5e-3"), Some(1.5e-3));
+        assert_eq!(float("2E10"), Some(2e10));
+        assert_eq!(float("3f32"), Some(3.0));
+        assert_eq!(float("1e400"), None);
+        assert_eq!(float("1e39f32"), None);
+
+        let note = |text: &str| Number::parse(text).unwrap().range_note();
+        assert_eq!(note("256u8"), "`u8` ints go from 0 to 255");
+        assert_eq!(note("129i8"), "`i8` ints go from -128 to 127");
+        assert_eq!(note("1e400"), "floats are 64 bit, up to about 1.8e308");
+        assert_eq!(note("1e39f32"), "`f32` floats go up to about 3.4e38");
+        assert!(note("99999999999999999999").starts_with("ints are 64 bit"));
+
+        let number = Number::parse("0x1f32").unwrap();
+        assert!(!number.float);
+        assert_eq!(number.suffix, None);
+        assert_eq!(Number::parse("10i32").unwrap().suffix, Some(Suffix::I32));
+
+        for malformed in ["0x", "0b102", "1e", "12ab", "1.5i32", "1.", "0o8"] {
+            assert_eq!(Number::parse(malformed), None, "{malformed}");
+        }
+    }
+}
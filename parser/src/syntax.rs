@@ -0,0 +1,772 @@
+//! A lossless syntax tree in the style of rowan: an immutable, position independent green tree
+//! holding the exact source text (trivia included), with a red layer on top that knows parents
+//! and offsets, and a typed view on top of that.
+
+use crate::lexer::Lexer;
+use crate::token::{Token, TokenKind};
+use crate::{ParseError, Parser};
+use std::cmp::Reverse;
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    Root,
+    LetStmt,
+    ExprStmt,
+    /// `for`, `while`, `break` and `continue`.
+    ControlStmt,
+    ParamList,
+    Param,
+    ArgList,
+    MatchArm,
+    Pattern,
+    Literal,
+    Name,
+    Binary,
+    Unary,
+    Range,
+    Paren,
+    List,
+    Call,
+    Lambda,
+    If,
+    Match,
+    Block,
+    Interpolation,
+    Token(TokenKind),
+    /// A statement or expression the parser skipped, or source text the lexer could not make a
+    /// token of.
+    Error,
+}
+
+impl SyntaxKind {
+    pub fn is_expr(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::Literal
+                | SyntaxKind::Name
+                | SyntaxKind::Binary
+                | SyntaxKind::Unary
+                | SyntaxKind::Range
+                | SyntaxKind::Paren
+                | SyntaxKind::List
+                | SyntaxKind::Call
+                | SyntaxKind::Lambda
+                | SyntaxKind::If
+                | SyntaxKind::Match
+                | SyntaxKind::Block
+                | SyntaxKind::Interpolation
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: String,
+}
+
+impl GreenToken {
+    pub fn new(kind: SyntaxKind, text: &str) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        let len = children.iter().map(GreenElement::len).sum();
+        Self {
+            kind,
+            len,
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// Returns a copy of this node with the child at `index` replaced.
+    pub fn replace_child(&self, index: usize, child: GreenElement) -> GreenNode {
+        let mut children = self.children.clone();
+        children[index] = child;
+        GreenNode::new(self.kind, children)
+    }
+}
+
+impl fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => write!(f, "{node}")?,
+                GreenElement::Token(token) => write!(f, "{}", token.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct GreenBuilder {
+    stack: Vec<(SyntaxKind, Vec<GreenElement>)>,
+    root: Option<GreenNode>,
+}
+
+impl GreenBuilder {
+    pub fn start_node(&mut self, kind: SyntaxKind) {
+        self.stack.push((kind, Vec::new()));
+    }
+
+    pub fn token(&mut self, kind: SyntaxKind, text: &str) {
+        let token = GreenElement::Token(Arc::new(GreenToken::new(kind, text)));
+        self.stack
+            .last_mut()
+            .expect("Tokens must be added inside a node.")
+            .1
+            .push(token);
+    }
+
+    pub fn finish_node(&mut self) {
+        let (kind, children) = self.stack.pop().expect("No node to finish.");
+        let node = GreenNode::new(kind, children);
+        match self.stack.last_mut() {
+            Some((_, parent)) => parent.push(GreenElement::Node(Arc::new(node))),
+            None => self.root = Some(node),
+        }
+    }
+
+    pub fn finish(self) -> GreenNode {
+        assert!(
+            self.stack.is_empty(),
+            "Unfinished nodes left in the builder."
+        );
+        self.root.expect("The builder never finished a root node.")
+    }
+}
+
+struct NodeData {
+    green: Arc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    index: usize,
+    offset: usize,
+}
+
+/// A node of the red tree, a cheap handle that knows its parent and absolute offset.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Arc<GreenToken>,
+    parent: SyntaxNode,
+    index: usize,
+    offset: usize,
+}
+
+#[derive(Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: GreenNode) -> Self {
+        SyntaxNode(Rc::new(NodeData {
+            green: Arc::new(green),
+            parent: None,
+            index: 0,
+            offset: 0,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &GreenNode {
+        &self.0.green
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.len
+    }
+
+    pub fn children_with_tokens(&self) -> impl Iterator<Item = SyntaxElement> + '_ {
+        let mut offset = self.0.offset;
+        self.0
+            .green
+            .children
+            .iter()
+            .enumerate()
+            .map(move |(index, child)| {
+                let start = offset;
+                offset += child.len();
+                match child {
+                    GreenElement::Node(green) => {
+                        SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                            green: green.clone(),
+                            parent: Some(self.clone()),
+                            index,
+                            offset: start,
+                        })))
+                    }
+                    GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                        green: green.clone(),
+                        parent: self.clone(),
+                        index,
+                        offset: start,
+                    }),
+                }
+            })
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        self.children_with_tokens().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = SyntaxToken> + '_ {
+        self.children_with_tokens().filter_map(|child| match child {
+            SyntaxElement::Node(_) => None,
+            SyntaxElement::Token(token) => Some(token),
+        })
+    }
+
+    /// Replaces this node, returning the green tree of the new root. Everything outside the
+    /// path from here to the root is shared with the old tree.
+    pub fn replace_with(&self, replacement: GreenNode) -> GreenNode {
+        match &self.0.parent {
+            Some(parent) => {
+                let child = GreenElement::Node(Arc::new(replacement));
+                parent.replace_with(parent.green().replace_child(self.0.index, child))
+            }
+            None => replacement,
+        }
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.green)
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn parent(&self) -> SyntaxNode {
+        self.parent.clone()
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text.len()
+    }
+
+    pub fn replace_with(&self, replacement: GreenToken) -> GreenNode {
+        let child = GreenElement::Token(Arc::new(replacement));
+        let parent = self.parent.green().replace_child(self.index, child);
+        self.parent.replace_with(parent)
+    }
+}
+
+pub struct Parse {
+    green: GreenNode,
+    errors: Vec<ParseError>,
+}
+
+impl Parse {
+    pub fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green.clone())
+    }
+
+    pub fn root(&self) -> Root {
+        Root(self.syntax())
+    }
+
+    /// The lex and parse errors, sorted by where they start.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+}
+
+/// Builds the syntax tree of `source`. The nodes are the ones [`Parser`] recorded while parsing
+/// the significant tokens; the trivia in between is put back into the innermost node around it.
+pub fn parse(source: &str) -> Parse {
+    let mut tokens = Vec::new();
+    let mut lex_errors = Vec::new();
+    for result in Lexer::with_trivia(source) {
+        match result {
+            Ok(token) => tokens.push(token),
+            Err(err) => lex_errors.push(err),
+        }
+    }
+
+    let significant = tokens
+        .iter()
+        .filter(|token| !token.kind.is_trivia())
+        .cloned()
+        .collect();
+    let mut parser = Parser::new(significant);
+    parser.lex_errors = lex_errors;
+    parser.prog();
+    let mut errors: Vec<_> = parser.lex_errors.into_iter().map(ParseError::Lex).collect();
+    errors.append(&mut parser.errors);
+    errors.sort_by_key(|err| err.span().start);
+
+    // Outer nodes first, and of two covering the same tokens the one recorded last.
+    let mut nodes: Vec<_> = parser.nodes.into_iter().enumerate().collect();
+    nodes.sort_by_key(|(index, (_, range))| (range.start, Reverse(range.end), Reverse(*index)));
+
+    let mut sink = Sink {
+        source,
+        tokens,
+        position: 0,
+        offset: 0,
+        builder: GreenBuilder::default(),
+    };
+    sink.builder.start_node(SyntaxKind::Root);
+    let mut nodes = nodes.into_iter().map(|(_, node)| node).peekable();
+    let mut open: Vec<usize> = Vec::new();
+    let mut index = 0;
+    loop {
+        while open.last() == Some(&index) {
+            sink.builder.finish_node();
+            open.pop();
+        }
+        if !sink.skip_trivia() {
+            break;
+        }
+        while let Some((kind, range)) = nodes.next_if(|(_, range)| range.start <= index) {
+            // Recovery can leave a node reaching past its parent; it is cut off there.
+            let end = open
+                .last()
+                .map_or(range.end, |&parent| range.end.min(parent));
+            if range.start == index && end > index {
+                sink.builder.start_node(kind);
+                open.push(end);
+            }
+        }
+        sink.bump();
+        index += 1;
+    }
+    sink.gap(source.len());
+    sink.builder.finish_node();
+
+    Parse {
+        green: sink.builder.finish(),
+        errors,
+    }
+}
+
+struct Sink<'a> {
+    source: &'a str,
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    offset: usize,
+    builder: GreenBuilder,
+}
+
+impl Sink<'_> {
+    /// Source text that no token covers (where the lexer reported an error) becomes an error
+    /// token, so nothing gets lost.
+    fn gap(&mut self, end: usize) {
+        if self.offset < end {
+            let text = &self.source[self.offset..end];
+            self.builder.token(SyntaxKind::Error, text);
+            self.offset = end;
+        }
+    }
+
+    fn bump(&mut self) {
+        let span = self.tokens[self.position].span;
+        let kind = self.tokens[self.position].kind;
+        self.gap(span.start);
+        self.builder
+            .token(SyntaxKind::Token(kind), &self.source[span.start..span.end]);
+        self.offset = span.end;
+        self.position += 1;
+    }
+
+    /// Adds the trivia up to the next significant token to the current node. Returns whether
+    /// there is such a token.
+    fn skip_trivia(&mut self) -> bool {
+        while let Some(token) = self.tokens.get(self.position) {
+            if !token.kind.is_trivia() {
+                return true;
+            }
+            self.bump();
+        }
+        false
+    }
+}
+
+/// A typed view of a syntax node.
+pub trait AstNode: Sized {
+    fn cast(node: SyntaxNode) -> Option<Self>;
+    fn syntax(&self) -> &SyntaxNode;
+}
+
+macro_rules! ast_node {
+    ($name:ident, $kind:ident) => {
+        #[derive(Clone)]
+        pub struct $name(SyntaxNode);
+
+        impl AstNode for $name {
+            fn cast(node: SyntaxNode) -> Option<Self> {
+                (node.kind() == SyntaxKind::$kind).then_some($name(node))
+            }
+
+            fn syntax(&self) -> &SyntaxNode {
+                &self.0
+            }
+        }
+    };
+}
+
+ast_node!(Root, Root);
+ast_node!(LetStmt, LetStmt);
+ast_node!(ExprStmt, ExprStmt);
+ast_node!(ControlStmt, ControlStmt);
+ast_node!(ParamList, ParamList);
+ast_node!(Param, Param);
+ast_node!(ArgList, ArgList);
+ast_node!(Block, Block);
+ast_node!(CallExpr, Call);
+ast_node!(LambdaExpr, Lambda);
+
+#[derive(Clone)]
+pub enum Stmt {
+    Let(LetStmt),
+    Expr(ExprStmt),
+    Control(ControlStmt),
+}
+
+impl AstNode for Stmt {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::LetStmt => Some(Stmt::Let(LetStmt(node))),
+            SyntaxKind::ExprStmt => Some(Stmt::Expr(ExprStmt(node))),
+            SyntaxKind::ControlStmt => Some(Stmt::Control(ControlStmt(node))),
+            _ => None,
+        }
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Stmt::Let(stmt) => stmt.syntax(),
+            Stmt::Expr(stmt) => stmt.syntax(),
+            Stmt::Control(stmt) => stmt.syntax(),
+        }
+    }
+}
+
+/// Any expression. Cast it to [`CallExpr`], [`LambdaExpr`] or [`Block`] to look inside those.
+#[derive(Clone)]
+pub struct Expr(SyntaxNode);
+
+impl AstNode for Expr {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        node.kind().is_expr().then_some(Expr(node))
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        &self.0
+    }
+}
+
+fn stmts(node: &SyntaxNode) -> impl Iterator<Item = Stmt> + '_ {
+    node.children().filter_map(Stmt::cast)
+}
+
+fn exprs(node: &SyntaxNode) -> impl Iterator<Item = Expr> + '_ {
+    node.children().filter_map(Expr::cast)
+}
+
+fn significant_tokens(node: &SyntaxNode) -> impl Iterator<Item = SyntaxToken> + '_ {
+    node.tokens().filter(|token| {
+        !matches!(
+            token.kind(),
+            SyntaxKind::Token(kind) if kind.is_trivia() || kind == TokenKind::DocComment
+        )
+    })
+}
+
+impl Root {
+    pub fn stmts(&self) -> impl Iterator<Item = Stmt> + '_ {
+        stmts(&self.0)
+    }
+}
+
+impl Block {
+    pub fn stmts(&self) -> impl Iterator<Item = Stmt> + '_ {
+        stmts(&self.0)
+    }
+}
+
+impl LetStmt {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        significant_tokens(&self.0)
+            .find(|token| {
+                !matches!(
+                    token.kind(),
+                    SyntaxKind::Token(TokenKind::Let | TokenKind::Mut)
+                )
+            })
+            .filter(|token| token.kind() == SyntaxKind::Token(TokenKind::Ident))
+    }
+
+    /// The parameter list, if this declares a function.
+    pub fn params(&self) -> Option<ParamList> {
+        self.0.children().find_map(ParamList::cast)
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        exprs(&self.0).next()
+    }
+
+    pub fn doc_comments(&self) -> impl Iterator<Item = SyntaxToken> + '_ {
+        self.0
+            .tokens()
+            .filter(|token| token.kind() == SyntaxKind::Token(TokenKind::DocComment))
+    }
+}
+
+impl ExprStmt {
+    /// The expression, or the target of an assignment.
+    pub fn expr(&self) -> Option<Expr> {
+        exprs(&self.0).next()
+    }
+
+    /// The value assigned, if this is an assignment.
+    pub fn value(&self) -> Option<Expr> {
+        exprs(&self.0).nth(1)
+    }
+}
+
+impl ControlStmt {
+    pub fn keyword(&self) -> Option<SyntaxToken> {
+        significant_tokens(&self.0).next()
+    }
+
+    pub fn body(&self) -> Option<Block> {
+        self.0.children().find_map(Block::cast)
+    }
+}
+
+impl ParamList {
+    pub fn params(&self) -> impl Iterator<Item = Param> + '_ {
+        self.0.children().filter_map(Param::cast)
+    }
+}
+
+impl Param {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        significant_tokens(&self.0).next()
+    }
+}
+
+impl Expr {
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.kind()
+    }
+
+    /// The expressions directly inside this one.
+    pub fn exprs(&self) -> impl Iterator<Item = Expr> + '_ {
+        exprs(&self.0)
+    }
+}
+
+impl CallExpr {
+    pub fn callee(&self) -> Option<Expr> {
+        exprs(&self.0).next()
+    }
+
+    pub fn arg_list(&self) -> Option<ArgList> {
+        self.0.children().find_map(ArgList::cast)
+    }
+}
+
+impl ArgList {
+    pub fn args(&self) -> impl Iterator<Item = Expr> + '_ {
+        exprs(&self.0)
+    }
+}
+
+impl LambdaExpr {
+    pub fn params(&self) -> Option<ParamList> {
+        self.0.children().find_map(ParamList::cast)
+    }
+
+    pub fn body(&self) -> Option<Expr> {
+        exprs(&self.0).next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::syntax::{
+        parse, AstNode, CallExpr, GreenToken, LambdaExpr, Stmt, SyntaxKind, SyntaxNode,
+    };
+    use crate::token::TokenKind;
+
+    const SOURCE: &str = "/// Adds.\nlet add(a, b: int) = a + b // sum\n\n\
+        while(x < 10) {\n    x = x + 1 ; print(\"{x}\")\n}\nlet y = 1 % 2\n";
+
+    /// The kinds of the nodes, outer ones first, leaving out the tokens.
+    fn outline(node: &SyntaxNode) -> String {
+        let children: Vec<_> = node.children().map(|child| outline(&child)).collect();
+        match children.is_empty() {
+            true => format!("{:?}", node.kind()),
+            false => format!("{:?}({})", node.kind(), children.join(" ")),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // Even broken code keeps all of its text.
+        for source in [
+            "let = (1,\n",
+            "}} { [1, 2\n",
+            "\"{a\" x # 1",
+            "match x { (a | 1 => }",
+        ] {
+            assert_eq!(parse(source).syntax().to_string(), source);
+        }
+
+        let parse = parse(SOURCE);
+        assert_eq!(parse.syntax().to_string(), SOURCE);
+        // Only the `%` the lexer doesn't know, not the `2` the parser didn't expect after it.
+        assert_eq!(parse.errors().len(), 1);
+
+        let stmts: Vec<_> = parse.root().stmts().collect();
+        assert_eq!(stmts.len(), 2);
+        let Stmt::Let(add) = &stmts[0] else {
+            panic!("expected a let statement");
+        };
+        assert_eq!(add.name().unwrap().text(), "add");
+        let params = add.params().unwrap();
+        assert_eq!(params.syntax().to_string(), "(a, b: int)");
+        let names: Vec<_> = params
+            .params()
+            .map(|param| param.name().unwrap().text().to_string())
+            .collect();
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!(add.value().unwrap().kind(), SyntaxKind::Binary);
+        assert_eq!(add.doc_comments().next().unwrap().text(), "/// Adds.");
+        assert_eq!(add.syntax().text_range(), 0..36);
+
+        let Stmt::Control(while_loop) = &stmts[1] else {
+            panic!("expected a while loop");
+        };
+        assert_eq!(while_loop.keyword().unwrap().text(), "while");
+        let body: Vec<_> = while_loop.body().unwrap().stmts().collect();
+        assert_eq!(body.len(), 2);
+        let Stmt::Expr(print) = &body[1] else {
+            panic!("expected an expression statement");
+        };
+        let call = CallExpr::cast(print.expr().unwrap().syntax().clone()).unwrap();
+        assert_eq!(call.callee().unwrap().syntax().to_string(), "print");
+        let args: Vec<_> = call.arg_list().unwrap().args().collect();
+        assert_eq!(args.len(), 1);
+        assert_eq!(args[0].kind(), SyntaxKind::Interpolation);
+
+        // The statement the parser gave up on is an error node, still with all of its text.
+        let error = parse.syntax().children().last().unwrap();
+        assert_eq!(error.kind(), SyntaxKind::Error);
+        assert_eq!(error.to_string(), "let y = 1 % 2");
+    }
+
+    #[test]
+    fn test_let_mut() {
+        let parse = parse("let mut x = 0");
+        let Some(Stmt::Let(stmt)) = parse.root().stmts().next() else {
+            panic!("expected a let statement");
+        };
+        assert_eq!(stmt.name().unwrap().text(), "x");
+        assert_eq!(stmt.value().unwrap().kind(), SyntaxKind::Literal);
+    }
+
+    #[test]
+    fn test_expressions() {
+        let parse = parse("apply(|x| {\n  x * 2\n}, -3 .. 4)\n");
+        assert_eq!(
+            outline(&parse.syntax()),
+            "Root(ExprStmt(Call(Name ArgList(Lambda(ParamList(Param) Block(ExprStmt(Binary(Name \
+             Literal)))) Range(Literal Literal)))))"
+        );
+        let lambda = parse
+            .syntax()
+            .children()
+            .next()
+            .and_then(|stmt| stmt.children().next())
+            .and_then(|call| call.children().nth(1))
+            .and_then(|args| args.children().next())
+            .and_then(LambdaExpr::cast)
+            .unwrap();
+        assert_eq!(lambda.params().unwrap().params().count(), 1);
+        assert_eq!(lambda.body().unwrap().kind(), SyntaxKind::Block);
+    }
+
+    #[test]
+    fn test_edit() {
+        let parse = parse(SOURCE);
+        let Some(Stmt::Let(add)) = parse.root().stmts().next() else {
+            panic!("expected a let statement");
+        };
+        let name = add.name().unwrap();
+        let green = name.replace_with(GreenToken::new(SyntaxKind::Token(TokenKind::Ident), "plus"));
+        assert_eq!(green.to_string(), SOURCE.replacen("add", "plus", 1));
+    }
+}
@@ -1,7 +1,13 @@
-#[derive(Clone, Copy, Debug, PartialEq)]
+use ast::{Span, Symbol};
+use std::borrow::Cow;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum TokenKind {
     RightArrow,
     LeftArrow,
+    FatArrow,
+    Pipe,
     LeftBrace,
     RightBrace,
     LeftBracket,
@@ -9,22 +15,39 @@ pub enum TokenKind {
     RightCurlyBracket,
     LeftCurlyBracket,
     String,
+    InterpStart,
+    InterpMid,
+    InterpEnd,
     Int,
     Float,
     Ident,
     Colon,
     Comma,
+    DotDot,
+    DotDotEq,
     Let,
+    Mut,
     If,
     Else,
     For,
     While,
     Match,
+    Break,
+    Continue,
+    True,
+    False,
     Eq,
+    Newline,
+    Semicolon,
+    DocComment,
+    Whitespace,
+    Comment,
     Op(Operator),
+    /// `+=`, `-=`, `*=` or `/=`.
+    AssignOp(Operator),
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Operator {
     Add,
     Sub,
@@ -35,29 +58,149 @@ pub enum Operator {
     MoreEq,
     Less,
     LessEq,
+    Not,
+}
+
+impl TokenKind {
+    pub fn is_trivia(self) -> bool {
+        matches!(self, TokenKind::Whitespace | TokenKind::Comment)
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            TokenKind::RightArrow => "`->`",
+            TokenKind::LeftArrow => "`-<`",
+            TokenKind::FatArrow => "`=>`",
+            TokenKind::Pipe => "`|`",
+            TokenKind::LeftBrace => "`(`",
+            TokenKind::RightBrace => "`)`",
+            TokenKind::LeftBracket => "`[`",
+            TokenKind::RightBracket => "`]`",
+            TokenKind::RightCurlyBracket => "`}`",
+            TokenKind::LeftCurlyBracket => "`{`",
+            TokenKind::String | TokenKind::InterpStart => "string",
+            TokenKind::InterpMid | TokenKind::InterpEnd => "part of a string",
+            TokenKind::Int => "int",
+            TokenKind::Float => "float",
+            TokenKind::Ident => "identifier",
+            TokenKind::Colon => "`:`",
+            TokenKind::Comma => "`,`",
+            TokenKind::DotDot => "`..`",
+            TokenKind::DotDotEq => "`..=`",
+            TokenKind::Let => "`let`",
+            TokenKind::Mut => "`mut`",
+            TokenKind::If => "`if`",
+            TokenKind::Else => "`else`",
+            TokenKind::For => "`for`",
+            TokenKind::While => "`while`",
+            TokenKind::Match => "`match`",
+            TokenKind::Break => "`break`",
+            TokenKind::Continue => "`continue`",
+            TokenKind::True => "`true`",
+            TokenKind::False => "`false`",
+            TokenKind::Eq => "`=`",
+            TokenKind::Newline => "line break",
+            TokenKind::Semicolon => "`;`",
+            TokenKind::DocComment => "doc comment",
+            TokenKind::Whitespace => "whitespace",
+            TokenKind::Comment => "comment",
+            TokenKind::Op(op) => return write!(f, "`{op}`"),
+            TokenKind::AssignOp(op) => return write!(f, "`{op}=`"),
+        };
+        write!(f, "{text}")
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Div => "/",
+            Operator::EqEq => "==",
+            Operator::More => ">",
+            Operator::MoreEq => ">=",
+            Operator::Less => "<",
+            Operator::LessEq => "<=",
+            Operator::Not => "!",
+        };
+        write!(f, "{text}")
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct Token {
+pub struct Token<'a> {
     pub kind: TokenKind,
-    pub value: String,
+    pub value: Cow<'a, str>,
+    /// The interned name of an identifier.
+    pub symbol: Option<Symbol>,
+    pub span: Span,
 }
 
-impl Token {
-    pub fn new(kind: TokenKind, value: String) -> Self {
-        Self { kind, value }
+impl<'a> Token<'a> {
+    pub fn new(kind: TokenKind, value: Cow<'a, str>, span: Span) -> Self {
+        match kind {
+            TokenKind::Ident => Self::ident(value, span),
+            kind => Self {
+                kind,
+                value,
+                symbol: None,
+                span,
+            },
+        }
+    }
+
+    fn ident(value: Cow<'a, str>, span: Span) -> Self {
+        Self {
+            kind: TokenKind::Ident,
+            symbol: Some(Symbol::intern(&value)),
+            value,
+            span,
+        }
     }
 
-    pub fn kw_or_ident(value: String) -> Self {
-        let kind = match value.as_str() {
+    pub fn kw_or_ident(value: Cow<'a, str>, span: Span) -> Self {
+        let kind = match value.as_ref() {
             "let" => TokenKind::Let,
+            "mut" => TokenKind::Mut,
             "if" => TokenKind::If,
             "else" => TokenKind::Else,
             "for" => TokenKind::For,
             "while" => TokenKind::While,
             "match" => TokenKind::Match,
-            _ => TokenKind::Ident,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
+            "true" => TokenKind::True,
+            "false" => TokenKind::False,
+            _ => return Self::ident(value, span),
         };
-        Self { kind, value }
+        Self {
+            kind,
+            value,
+            symbol: None,
+            span,
+        }
+    }
+
+    /// Describes the token for error messages, quoting the text of names and literals.
+    pub fn describe(&self) -> String {
+        match self.kind {
+            TokenKind::Ident | TokenKind::Int | TokenKind::Float => {
+                format!("{} `{}`", self.kind, self.value)
+            }
+            kind => kind.to_string(),
+        }
+    }
+
+    pub fn into_owned(self) -> Token<'static> {
+        Token {
+            kind: self.kind,
+            value: Cow::Owned(self.value.into_owned()),
+            symbol: self.symbol,
+            span: self.span,
+        }
     }
 }
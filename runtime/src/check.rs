@@ -0,0 +1,361 @@
+use ast::{
+    Diagnostic, Expr, ExprKind, Mutability, Pattern, PatternKind, Span, Stmt, StmtKind, StringPart,
+    Symbol, ValueKind,
+};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CheckError {
+    /// Assignment to a variable that wasn't declared with `mut`. The second span is where it was
+    /// declared.
+    Immutable(Symbol, Span, Span),
+    Undeclared(Symbol, Span),
+    /// An alternative of an or-pattern that doesn't bind a variable another one does. The second
+    /// span is where the other one binds it.
+    Unbound(Symbol, Span, Span),
+}
+
+impl CheckError {
+    pub fn span(&self) -> Span {
+        match self {
+            CheckError::Immutable(_, span, _) => *span,
+            CheckError::Undeclared(_, span) => *span,
+            CheckError::Unbound(_, span, _) => *span,
+        }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        match self {
+            CheckError::Immutable(name, span, declared) => {
+                Diagnostic::error(format!("cannot assign to immutable variable `{name}`"))
+                    .with_label(*span, "cannot assign to it")
+                    .with_secondary(*declared, format!("`{name}` is declared here"))
+                    .with_help("only variables declared with `let mut` can be assigned to")
+            }
+            CheckError::Undeclared(name, span) => {
+                Diagnostic::error(format!("cannot find variable `{name}` to assign to"))
+                    .with_label(*span, "not found in this scope")
+                    .with_help(format!("declare it first with `let mut {name} = ..`"))
+            }
+            CheckError::Unbound(name, span, bound) => Diagnostic::error(format!(
+                "variable `{name}` is not bound in all alternatives"
+            ))
+            .with_label(*span, format!("pattern doesn't bind `{name}`"))
+            .with_secondary(*bound, "variable not in all alternatives"),
+        }
+    }
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.diagnostic().message)
+    }
+}
+
+impl std::error::Error for CheckError {}
+
+#[derive(Clone, Copy)]
+struct Binding {
+    mutability: Mutability,
+    span: Span,
+}
+
+/// Walks the program with the same scopes as the interpreter, keeping track of what each name
+/// refers to.
+struct Checker<'a> {
+    scopes: Vec<HashMap<Symbol, Binding>>,
+    /// Function bodies and their parameters, for each scope. A function sees its scope as it is
+    /// when it is called, including variables declared after it, so bodies are only checked once
+    /// the scope is complete.
+    bodies: Vec<Vec<(HashMap<Symbol, Binding>, &'a Expr)>>,
+    errors: Vec<CheckError>,
+}
+
+/// Finds the mistakes that don't need the program to run: assignments to variables that don't
+/// exist or aren't `mut`.
+pub fn check(ast: &ast::Ast) -> Vec<CheckError> {
+    let mut checker = Checker {
+        scopes: vec![HashMap::new()],
+        bodies: vec![Vec::new()],
+        errors: Vec::new(),
+    };
+    for stmt in &ast.prog {
+        checker.stmt(stmt);
+    }
+    checker.bodies();
+    checker.errors.sort_by_key(|err| err.span().start);
+    checker.errors
+}
+
+impl<'a> Checker<'a> {
+    fn declare(&mut self, name: Symbol, mutability: Mutability, span: Span) {
+        let binding = Binding { mutability, span };
+        self.scopes.last_mut().unwrap().insert(name, binding);
+    }
+
+    fn lookup(&self, name: &Symbol) -> Option<Binding> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+
+    fn scoped(&mut self, scope: HashMap<Symbol, Binding>, check: impl FnOnce(&mut Self)) {
+        self.scopes.push(scope);
+        self.bodies.push(Vec::new());
+        check(self);
+        self.bodies();
+        self.bodies.pop();
+        self.scopes.pop();
+    }
+
+    /// Checks the bodies of the functions made in the innermost scope.
+    fn bodies(&mut self) {
+        let bodies = std::mem::take(self.bodies.last_mut().unwrap());
+        for (params, body) in bodies {
+            self.scoped(params, |checker| checker.expr(body));
+        }
+    }
+
+    /// Leaves `body` to be checked at the end of the current scope.
+    fn body(&mut self, params: &ast::Params, span: Span, body: &'a Expr) {
+        let binding = Binding {
+            mutability: Mutability::Not,
+            span,
+        };
+        let params = params
+            .iter()
+            .map(|(name, _)| (name.clone(), binding))
+            .collect();
+        self.bodies.last_mut().unwrap().push((params, body));
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt) {
+        match &stmt.kind {
+            StmtKind::Let(name, mutability, _, expr) => {
+                self.expr(expr);
+                self.declare(name.clone(), *mutability, stmt.span);
+            }
+            StmtKind::Func(name, params, _, body) => {
+                self.declare(name.clone(), Mutability::Not, stmt.span);
+                self.body(params, stmt.span, body);
+            }
+            StmtKind::For(name, iter, body) => {
+                self.expr(iter);
+                let binding = Binding {
+                    mutability: Mutability::Not,
+                    span: stmt.span,
+                };
+                self.scoped(HashMap::from([(name.clone(), binding)]), |checker| {
+                    checker.stmt(body)
+                });
+            }
+            StmtKind::While(cond, body) => {
+                self.expr(cond);
+                self.stmt(body);
+            }
+            StmtKind::Assign(target, _, value) => {
+                self.expr(value);
+                let ExprKind::Value(ast::Value {
+                    kind: ValueKind::Ident(name),
+                    span,
+                }) = &target.kind
+                else {
+                    return self.expr(target);
+                };
+                match self.lookup(name) {
+                    Some(binding) if binding.mutability == Mutability::Not => self
+                        .errors
+                        .push(CheckError::Immutable(name.clone(), *span, binding.span)),
+                    Some(_) => {}
+                    None => self
+                        .errors
+                        .push(CheckError::Undeclared(name.clone(), *span)),
+                }
+            }
+            StmtKind::Expr(expr) => self.expr(expr),
+            StmtKind::Break | StmtKind::Continue | StmtKind::Error => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Expr) {
+        match &expr.kind {
+            ExprKind::Value(_) | ExprKind::Error => {}
+            ExprKind::Call(callee, args) => {
+                self.expr(callee);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            ExprKind::Binary(lhs, _, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ExprKind::Unary(_, expr) => self.expr(expr),
+            ExprKind::Range(start, end, _, step) => {
+                self.expr(start);
+                self.expr(end);
+                if let Some(step) = step {
+                    self.expr(step);
+                }
+            }
+            ExprKind::List(elems) | ExprKind::Tuple(elems) => {
+                elems.iter().for_each(|elem| self.expr(elem))
+            }
+            ExprKind::Match(scrutinee, cases) => {
+                self.expr(scrutinee);
+                for (pattern, guard, body) in cases {
+                    let mut scope = HashMap::new();
+                    self.bindings(pattern, &mut scope);
+                    self.scoped(scope, |checker| {
+                        if let Some(guard) = guard {
+                            checker.expr(guard);
+                        }
+                        checker.expr(body);
+                    });
+                }
+            }
+            ExprKind::Lambda(params, body) => self.body(params, expr.span, body),
+            ExprKind::Block(stmts) => self.scoped(HashMap::new(), |checker| {
+                stmts.iter().for_each(|stmt| checker.stmt(stmt))
+            }),
+            ExprKind::If(cond, then, otherwise) => {
+                self.expr(cond);
+                self.expr(then);
+                if let Some(otherwise) = otherwise {
+                    self.expr(otherwise);
+                }
+            }
+            ExprKind::Interpolation(parts) => {
+                for part in parts {
+                    if let StringPart::Expr(expr) = part {
+                        self.expr(expr);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Collects the variables a pattern binds, none of which are mutable. Every alternative of an
+    /// or-pattern has to bind the same ones.
+    fn bindings(&mut self, pattern: &Pattern, scope: &mut HashMap<Symbol, Binding>) {
+        match &pattern.kind {
+            PatternKind::Binding(name) => {
+                let binding = Binding {
+                    mutability: Mutability::Not,
+                    span: pattern.span,
+                };
+                scope.insert(name.clone(), binding);
+            }
+            PatternKind::Tuple(patterns) | PatternKind::List(patterns) => patterns
+                .iter()
+                .for_each(|pattern| self.bindings(pattern, scope)),
+            PatternKind::Or(alternatives) => {
+                let alternatives: Vec<_> = alternatives
+                    .iter()
+                    .map(|alternative| {
+                        let mut bound = HashMap::new();
+                        self.bindings(alternative, &mut bound);
+                        (alternative.span, bound)
+                    })
+                    .collect();
+                let mut names: Vec<_> = alternatives.iter().flat_map(|(_, bound)| bound).collect();
+                names.sort_by_key(|(_, binding)| binding.span.start);
+                for (span, bound) in &alternatives {
+                    let mut reported = Vec::new();
+                    for (name, binding) in &names {
+                        if !bound.contains_key(name) && !reported.contains(name) {
+                            reported.push(*name);
+                            let err = CheckError::Unbound((*name).clone(), *span, binding.span);
+                            self.errors.push(err);
+                        }
+                    }
+                }
+                for (_, bound) in alternatives {
+                    scope.extend(bound);
+                }
+            }
+            PatternKind::Struct(_, fields) => fields
+                .iter()
+                .for_each(|(_, pattern)| self.bindings(pattern, scope)),
+            PatternKind::Wildcard
+            | PatternKind::Literal(_)
+            | PatternKind::Range(..)
+            | PatternKind::Rest => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::check::{check, CheckError};
+    use ast::Span;
+    use parser::{lexer::Lexer, Parser};
+
+    fn errors(source: &str) -> Vec<CheckError> {
+        check(&Parser::from_lexer(Lexer::new(source)).parse().unwrap())
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(errors("let mut x = 1\nx = 2\nx += 3"), []);
+        assert_eq!(
+            errors("let x = 1\nx *= 2"),
+            [CheckError::Immutable(
+                "x".into(),
+                Span::new(10, 11, 2, 1),
+                Span::new(0, 9, 1, 1)
+            )]
+        );
+        assert_eq!(
+            errors("if 1 == 1 { let mut y = 1 }\ny = 2"),
+            [CheckError::Undeclared("y".into(), Span::new(28, 29, 2, 1))]
+        );
+
+        // The innermost binding decides, whether it shadows a `mut` one or is shadowed by one.
+        assert_eq!(
+            errors("let mut a = 1\nwhile a < 2 { let a = 2; a = 3 }").len(),
+            1
+        );
+        assert_eq!(
+            errors("let a = 1\nwhile a < 2 { let mut a = 2; a = 3 }"),
+            []
+        );
+
+        assert!(matches!(
+            errors("for i <= 0 .. 3 { i = 1 }")[..],
+            [CheckError::Immutable(..)]
+        ));
+        assert!(matches!(
+            errors("match 1 { n if n > 0 => { n = 0 }, _ => 1 }")[..],
+            [CheckError::Immutable(..)]
+        ));
+
+        assert_eq!(
+            errors("match (0, 1) { (x, 0) | (0, y) => x, _ => 0 }"),
+            [
+                CheckError::Unbound(
+                    "y".into(),
+                    Span::new(15, 21, 1, 16),
+                    Span::new(28, 29, 1, 29)
+                ),
+                CheckError::Unbound(
+                    "x".into(),
+                    Span::new(24, 30, 1, 25),
+                    Span::new(16, 17, 1, 17)
+                ),
+            ]
+        );
+        assert_eq!(errors("match (0, 1) { (x, 0) | (0, x) => x, _ => 0 }"), []);
+
+        // Functions see the variables of their scope declared after them.
+        assert_eq!(errors("let f() = { x = 1 }\nlet mut x = 0\nf()\nx"), []);
+        assert!(matches!(
+            errors("let f = || { x = 1 }\nlet x = 0")[..],
+            [CheckError::Immutable(..)]
+        ));
+        assert!(matches!(
+            errors("{ let f() = { y = 1 } }\nlet mut y = 0")[..],
+            [CheckError::Undeclared(..)]
+        ));
+    }
+}
@@ -0,0 +1,1041 @@
+use crate::value::{Builtin, Closure, Range, Scope, Value};
+use ast::{
+    Diagnostic, Expr, ExprKind, Operation, Pattern, PatternKind, RangeLimits, Span, Stmt, StmtKind,
+    StringPart, Symbol, UnaryOp, ValueKind,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::io::{self, Write as _};
+use std::rc::Rc;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuntimeError {
+    UnboundVariable(Symbol, Span),
+    Type(String, Span),
+    DivisionByZero(Span),
+    Overflow(Span),
+    Unsupported(String, Span),
+    /// No arm of a match expression matched the value.
+    NoMatch(Span),
+    /// Unwinds to the innermost loop. Only an error if there is none.
+    Break(Span),
+    Continue(Span),
+    /// A `break` or `continue` in a function body that isn't inside a loop there.
+    OutsideLoop(&'static str, Span),
+    /// Writing the output of `print` failed.
+    Output(String, Span),
+}
+
+impl RuntimeError {
+    pub fn span(&self) -> Span {
+        match self {
+            RuntimeError::UnboundVariable(_, span) => *span,
+            RuntimeError::Type(_, span) => *span,
+            RuntimeError::DivisionByZero(span) => *span,
+            RuntimeError::Overflow(span) => *span,
+            RuntimeError::Unsupported(_, span) => *span,
+            RuntimeError::NoMatch(span) => *span,
+            RuntimeError::Break(span) => *span,
+            RuntimeError::Continue(span) => *span,
+            RuntimeError::OutsideLoop(_, span) => *span,
+            RuntimeError::Output(_, span) => *span,
+        }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let span = self.span();
+        match self {
+            RuntimeError::UnboundVariable(name, _) => {
+                Diagnostic::error(format!("cannot find variable `{name}`"))
+                    .with_label(span, "not found in this scope")
+            }
+            RuntimeError::Type(message, _) => Diagnostic::error(message).with_label(span, ""),
+            RuntimeError::DivisionByZero(_) => {
+                Diagnostic::error("division by zero").with_label(span, "the divisor is zero")
+            }
+            RuntimeError::Overflow(_) => Diagnostic::error("arithmetic overflow")
+                .with_label(span, "the result doesn't fit in a 64 bit int"),
+            RuntimeError::Unsupported(what, _) => {
+                Diagnostic::error(format!("this {} can't be run yet", what.to_lowercase()))
+                    .with_label(span, "")
+            }
+            RuntimeError::NoMatch(_) => Diagnostic::error("no match arm matches the value")
+                .with_label(span, "")
+                .with_help("add a `_ => ..` arm for everything else"),
+            RuntimeError::Break(_) => {
+                Diagnostic::error("`break` outside of a loop").with_label(span, "")
+            }
+            RuntimeError::Continue(_) => {
+                Diagnostic::error("`continue` outside of a loop").with_label(span, "")
+            }
+            RuntimeError::OutsideLoop(keyword, _) => {
+                Diagnostic::error(format!("`{keyword}` outside of a loop"))
+                    .with_label(span, "")
+                    .with_note("a loop doesn't reach into the functions called from it")
+            }
+            RuntimeError::Output(err, _) => {
+                Diagnostic::error(format!("cannot write the output: {err}")).with_label(span, "")
+            }
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.diagnostic().message)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+type EvalResult = Result<Value, RuntimeError>;
+
+/// Variables live in a stack of scopes, innermost last. Blocks push a scope for their duration.
+pub struct Interpreter {
+    scopes: Vec<Scope>,
+    /// Where `print` writes to.
+    output: Box<dyn io::Write>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::with_output(io::stdout())
+    }
+}
+
+/// Lets go of the functions declared at the top level, see [`release`].
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        for scope in self.scopes.drain(..).rev() {
+            release(scope);
+        }
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_output(output: impl io::Write + 'static) -> Self {
+        Self {
+            scopes: vec![Scope::default()],
+            output: Box::new(output),
+        }
+    }
+
+    fn lookup(&self, name: &Symbol) -> Option<Value> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.borrow().get(name).cloned())
+    }
+
+    fn declare(&mut self, name: Symbol, value: Value) {
+        self.scopes.last().unwrap().borrow_mut().insert(name, value);
+    }
+
+    /// Runs `run` with `variables` in a new innermost scope.
+    fn scoped<T>(
+        &mut self,
+        variables: HashMap<Symbol, Value>,
+        run: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        self.scopes.push(Rc::new(RefCell::new(variables)));
+        let result = run(self);
+        release(self.scopes.pop().unwrap());
+        result
+    }
+
+    /// A block is worth its last statement if that is an expression, and unit otherwise, which is
+    /// what every other statement evaluates to.
+    fn block(&mut self, stmts: &[Stmt]) -> EvalResult {
+        self.scoped(HashMap::new(), |interpreter| {
+            stmts
+                .iter()
+                .try_fold(Value::Unit, |_, stmt| interpreter.exec(stmt))
+        })
+    }
+
+    pub fn run(&mut self, ast: &ast::Ast) -> EvalResult {
+        let mut value = Value::Unit;
+        for stmt in &ast.prog {
+            value = self.exec(stmt)?;
+        }
+        Ok(value)
+    }
+
+    pub fn exec(&mut self, stmt: &Stmt) -> EvalResult {
+        match &stmt.kind {
+            StmtKind::Let(name, _, _, expr) => {
+                let value = self.eval(expr)?;
+                self.declare(name.clone(), value);
+                Ok(Value::Unit)
+            }
+            StmtKind::While(cond, body) => {
+                while self.condition(cond)? {
+                    if !self.iteration(body)? {
+                        break;
+                    }
+                }
+                Ok(Value::Unit)
+            }
+            StmtKind::For(name, iter, body) => {
+                let items: Box<dyn Iterator<Item = Value>> = match self.eval(iter)? {
+                    Value::Range(range) => Box::new(range.iter().map(Value::Int)),
+                    Value::List(values) => Box::new(values.into_iter()),
+                    Value::String(string) => Box::new(
+                        string
+                            .chars()
+                            .map(|c| Value::String(c.into()))
+                            .collect::<Vec<_>>()
+                            .into_iter(),
+                    ),
+                    value => {
+                        return Err(RuntimeError::Type(
+                            format!("cannot iterate over {}", value.type_name()),
+                            iter.span,
+                        ))
+                    }
+                };
+                for item in items {
+                    let iteration = self
+                        .scoped(HashMap::from([(name.clone(), item)]), |interpreter| {
+                            interpreter.iteration(body)
+                        });
+                    if !iteration? {
+                        break;
+                    }
+                }
+                Ok(Value::Unit)
+            }
+            StmtKind::Assign(target, op, value) => {
+                let ExprKind::Value(ast::Value {
+                    kind: ValueKind::Ident(name),
+                    span,
+                }) = &target.kind
+                else {
+                    return Err(RuntimeError::Unsupported("Assignment".into(), target.span));
+                };
+                let mut value = self.eval(value)?;
+                if let Some(op) = op {
+                    let current = self.eval(target)?;
+                    value = binary(current, *op, value, stmt.span)?;
+                }
+                let scope = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .find(|scope| scope.borrow().contains_key(name));
+                match scope {
+                    Some(scope) => scope.borrow_mut().insert(name.clone(), value),
+                    None => return Err(RuntimeError::UnboundVariable(name.clone(), *span)),
+                };
+                Ok(Value::Unit)
+            }
+            StmtKind::Func(name, params, _, body) => {
+                let func = self.closure(Some(name.clone()), params, body);
+                self.declare(name.clone(), func);
+                Ok(Value::Unit)
+            }
+            StmtKind::Break => Err(RuntimeError::Break(stmt.span)),
+            StmtKind::Continue => Err(RuntimeError::Continue(stmt.span)),
+            StmtKind::Expr(expr) => self.eval(expr),
+            _ => Err(RuntimeError::Unsupported("Statement".into(), stmt.span)),
+        }
+    }
+
+    pub fn eval(&mut self, expr: &Expr) -> EvalResult {
+        match &expr.kind {
+            ExprKind::Value(value) => self.value(value),
+            ExprKind::Binary(lhs, op, rhs) => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                binary(lhs, *op, rhs, expr.span)
+            }
+            ExprKind::Unary(op, operand) => {
+                let value = self.eval(operand)?;
+                unary(*op, value, expr.span)
+            }
+            ExprKind::Block(stmts) => self.block(stmts),
+            ExprKind::Lambda(params, body) => Ok(self.closure(None, params, body)),
+            ExprKind::Call(callee, args) => {
+                let func = match self.eval(callee)? {
+                    func @ (Value::Func(_) | Value::Builtin(_)) => func,
+                    value => {
+                        return Err(RuntimeError::Type(
+                            format!("cannot call {}", value.type_name()),
+                            callee.span,
+                        ))
+                    }
+                };
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg))
+                    .collect::<Result<_, _>>()?;
+                match func {
+                    Value::Builtin(builtin) => self.builtin(builtin, args, expr.span),
+                    Value::Func(func) => self.call(&func, args, expr.span),
+                    _ => unreachable!("only functions are called"),
+                }
+            }
+            ExprKind::List(elems) => {
+                let values = elems
+                    .iter()
+                    .map(|elem| self.eval(elem))
+                    .collect::<Result<_, _>>()?;
+                Ok(Value::List(values))
+            }
+            ExprKind::Tuple(elems) if elems.is_empty() => Ok(Value::Unit),
+            ExprKind::Tuple(elems) => {
+                let values = elems
+                    .iter()
+                    .map(|elem| self.eval(elem))
+                    .collect::<Result<_, _>>()?;
+                Ok(Value::Tuple(values))
+            }
+            ExprKind::Match(scrutinee, cases) => {
+                let value = self.eval(scrutinee)?;
+                for (pattern, guard, body) in cases {
+                    let mut bindings = HashMap::new();
+                    if !matches(pattern, &value, &mut bindings)? {
+                        continue;
+                    }
+                    // The bindings are visible in the guard and the body only.
+                    let result = self.scoped(bindings, |interpreter| {
+                        let result = match guard {
+                            Some(guard) => interpreter.condition(guard),
+                            None => Ok(true),
+                        };
+                        match result {
+                            Ok(true) => interpreter.eval(body).map(Some),
+                            Ok(false) => Ok(None),
+                            Err(err) => Err(err),
+                        }
+                    });
+                    if let Some(value) = result? {
+                        return Ok(value);
+                    }
+                }
+                Err(RuntimeError::NoMatch(scrutinee.span))
+            }
+            ExprKind::Range(start, end, limits, step) => {
+                let step = match step {
+                    Some(step) => match self.int(step)? {
+                        step if step > 0 => step,
+                        _ => {
+                            return Err(RuntimeError::Type(
+                                "a range step must be positive".into(),
+                                step.span,
+                            ))
+                        }
+                    },
+                    None => 1,
+                };
+                Ok(Value::Range(Range {
+                    start: self.int(start)?,
+                    end: self.int(end)?,
+                    inclusive: *limits == RangeLimits::Inclusive,
+                    step,
+                }))
+            }
+            ExprKind::If(cond, then, otherwise) => match (self.condition(cond)?, otherwise) {
+                (true, _) => self.eval(then),
+                (false, Some(otherwise)) => self.eval(otherwise),
+                (false, None) => Ok(Value::Unit),
+            },
+            ExprKind::Interpolation(parts) => {
+                let mut string = String::new();
+                for part in parts {
+                    match part {
+                        StringPart::Literal(literal) => string.push_str(literal),
+                        StringPart::Expr(expr) => {
+                            let value = self.eval(expr)?;
+                            write!(string, "{value}").unwrap();
+                        }
+                    }
+                }
+                Ok(Value::String(string))
+            }
+            _ => Err(RuntimeError::Unsupported("Expression".into(), expr.span)),
+        }
+    }
+
+    fn closure(&self, name: Option<Symbol>, params: &ast::Params, body: &Expr) -> Value {
+        Value::Func(Rc::new(Closure {
+            name,
+            params: params.iter().map(|(name, _)| name.clone()).collect(),
+            body: body.clone(),
+            env: self.scopes.clone(),
+        }))
+    }
+
+    /// Runs the body with only the captured scopes and the arguments in scope.
+    fn call(&mut self, func: &Rc<Closure>, args: Vec<Value>, span: Span) -> EvalResult {
+        if args.len() != func.params.len() {
+            return Err(RuntimeError::Type(
+                format!(
+                    "expected {} arguments, found {}",
+                    func.params.len(),
+                    args.len()
+                ),
+                span,
+            ));
+        }
+        let mut variables: HashMap<_, _> = func.params.iter().cloned().zip(args).collect();
+        if let Some(name) = &func.name {
+            variables
+                .entry(name.clone())
+                .or_insert_with(|| Value::Func(func.clone()));
+        }
+
+        let scopes = std::mem::replace(&mut self.scopes, func.env.clone());
+        let value = self.scoped(variables, |interpreter| interpreter.eval(&func.body));
+        self.scopes = scopes;
+        match value {
+            Err(RuntimeError::Break(span)) => Err(RuntimeError::OutsideLoop("break", span)),
+            Err(RuntimeError::Continue(span)) => Err(RuntimeError::OutsideLoop("continue", span)),
+            value => value,
+        }
+    }
+
+    fn builtin(&mut self, builtin: Builtin, args: Vec<Value>, span: Span) -> EvalResult {
+        match builtin {
+            Builtin::Print => {
+                let line: Vec<_> = args.iter().map(Value::to_string).collect();
+                writeln!(self.output, "{}", line.join(" "))
+                    .map_err(|err| RuntimeError::Output(err.to_string(), span))?;
+                Ok(Value::Unit)
+            }
+        }
+    }
+
+    /// Runs one pass of a loop body, returning whether the loop goes on.
+    fn iteration(&mut self, body: &Stmt) -> Result<bool, RuntimeError> {
+        match self.exec(body) {
+            Ok(_) | Err(RuntimeError::Continue(_)) => Ok(true),
+            Err(RuntimeError::Break(_)) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn int(&mut self, expr: &Expr) -> Result<i64, RuntimeError> {
+        match self.eval(expr)? {
+            Value::Int(int) => Ok(int),
+            value => Err(RuntimeError::Type(
+                format!("expected an int, found {}", value.type_name()),
+                expr.span,
+            )),
+        }
+    }
+
+    fn condition(&mut self, cond: &Expr) -> Result<bool, RuntimeError> {
+        match self.eval(cond)? {
+            Value::Bool(bool) => Ok(bool),
+            value => Err(RuntimeError::Type(
+                format!("expected a bool condition, found {}", value.type_name()),
+                cond.span,
+            )),
+        }
+    }
+
+    fn value(&mut self, value: &ast::Value) -> EvalResult {
+        match &value.kind {
+            ValueKind::Int(int) => Ok(Value::Int(*int)),
+            ValueKind::Float(float) => Ok(Value::Float(*float)),
+            ValueKind::String(string) => Ok(Value::String(string.clone())),
+            ValueKind::Bool(bool) => Ok(Value::Bool(*bool)),
+            ValueKind::Ident(name) => match self.lookup(name) {
+                Some(value) => Ok(value),
+                None => match Builtin::named(name) {
+                    Some(builtin) => Ok(Value::Builtin(builtin)),
+                    None => Err(RuntimeError::UnboundVariable(name.clone(), value.span)),
+                },
+            },
+        }
+    }
+}
+
+/// Functions keep the scope they are made in alive, so one stored in that scope keeps both alive
+/// for good. Once nothing but such functions refer to a scope that is left, its variables are
+/// dropped to break the cycle.
+fn release(scope: Scope) {
+    // Without other references, no function made in the scope is left to keep it alive.
+    if Rc::strong_count(&scope) == 1 {
+        return;
+    }
+    let internal = {
+        let variables = scope.borrow();
+        let mut closures = Vec::new();
+        for value in variables.values() {
+            closures_in(value, &mut closures);
+        }
+        let mut internal = 0;
+        for (i, closure) in closures.iter().enumerate() {
+            if closures[..i].iter().any(|other| Rc::ptr_eq(other, closure)) {
+                continue;
+            }
+            let captures = closure.env.iter().filter(|env| Rc::ptr_eq(env, &scope));
+            let captures = captures.count();
+            // A function that can still be called from elsewhere needs the scope.
+            let occurrences = closures.iter().filter(|other| Rc::ptr_eq(other, closure));
+            if captures > 0 && Rc::strong_count(closure) != occurrences.count() {
+                return;
+            }
+            internal += captures;
+        }
+        internal
+    };
+    if Rc::strong_count(&scope) == 1 + internal {
+        let variables = std::mem::take(&mut *scope.borrow_mut());
+        drop(variables);
+    }
+}
+
+fn closures_in<'a>(value: &'a Value, closures: &mut Vec<&'a Rc<Closure>>) {
+    match value {
+        Value::Func(closure) => closures.push(closure),
+        Value::List(values) | Value::Tuple(values) => {
+            for value in values {
+                closures_in(value, closures);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether `value` fits `pattern`, collecting the variables it binds.
+fn matches(
+    pattern: &Pattern,
+    value: &Value,
+    bindings: &mut HashMap<Symbol, Value>,
+) -> Result<bool, RuntimeError> {
+    let matched = match (&pattern.kind, value) {
+        (PatternKind::Wildcard | PatternKind::Rest, _) => true,
+        (PatternKind::Binding(name), value) => {
+            bindings.insert(name.clone(), value.clone());
+            true
+        }
+        (PatternKind::Literal(literal), value) => match (&literal.kind, value) {
+            (ValueKind::Int(lhs), Value::Int(rhs)) => lhs == rhs,
+            (ValueKind::Float(lhs), Value::Float(rhs)) => lhs == rhs,
+            (ValueKind::String(lhs), Value::String(rhs)) => lhs == rhs,
+            (ValueKind::Bool(lhs), Value::Bool(rhs)) => lhs == rhs,
+            _ => false,
+        },
+        (PatternKind::Range(start, end, limits), value) => {
+            let inclusive = *limits == RangeLimits::Inclusive;
+            match (&start.kind, &end.kind, value) {
+                (ValueKind::Int(start), ValueKind::Int(end), Value::Int(value)) => {
+                    within(start, end, inclusive, value)
+                }
+                (ValueKind::Float(start), ValueKind::Float(end), Value::Float(value)) => {
+                    within(start, end, inclusive, value)
+                }
+                _ => false,
+            }
+        }
+        (PatternKind::Tuple(patterns), Value::Unit) => patterns.is_empty(),
+        (PatternKind::Tuple(patterns), Value::Tuple(values))
+        | (PatternKind::List(patterns), Value::List(values)) => {
+            sequence(patterns, values, bindings)?
+        }
+        (PatternKind::Or(alternatives), value) => {
+            for alternative in alternatives {
+                let mut alternative_bindings = HashMap::new();
+                if matches(alternative, value, &mut alternative_bindings)? {
+                    bindings.extend(alternative_bindings);
+                    return Ok(true);
+                }
+            }
+            false
+        }
+        (PatternKind::Struct(..), _) => {
+            return Err(RuntimeError::Unsupported(
+                "Struct pattern".into(),
+                pattern.span,
+            ))
+        }
+        _ => false,
+    };
+    Ok(matched)
+}
+
+fn within<T: PartialOrd>(start: T, end: T, inclusive: bool, value: T) -> bool {
+    start <= value && if inclusive { value <= end } else { value < end }
+}
+
+/// Matches the elements of a tuple or list. A `..` stands for any number of elements.
+fn sequence(
+    patterns: &[Pattern],
+    values: &[Value],
+    bindings: &mut HashMap<Symbol, Value>,
+) -> Result<bool, RuntimeError> {
+    let (before, after) = match patterns
+        .iter()
+        .position(|pattern| pattern.kind == PatternKind::Rest)
+    {
+        Some(rest) => (&patterns[..rest], &patterns[rest + 1..]),
+        None if patterns.len() == values.len() => (patterns, &[][..]),
+        None => return Ok(false),
+    };
+    if before.len() + after.len() > values.len() {
+        return Ok(false);
+    }
+    let tail = &values[values.len() - after.len()..];
+    for (pattern, value) in before.iter().zip(values).chain(after.iter().zip(tail)) {
+        if !matches(pattern, value, bindings)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn binary(lhs: Value, op: Operation, rhs: Value, span: Span) -> EvalResult {
+    match (lhs, rhs) {
+        (Value::Int(lhs), Value::Int(rhs)) => {
+            let value = match op {
+                Operation::Add => lhs.checked_add(rhs),
+                Operation::Sub => lhs.checked_sub(rhs),
+                Operation::Mul => lhs.checked_mul(rhs),
+                Operation::Div if rhs == 0 => return Err(RuntimeError::DivisionByZero(span)),
+                Operation::Div => lhs.checked_div(rhs),
+                op => return Ok(Value::Bool(compare(lhs, op, rhs))),
+            };
+            value.map(Value::Int).ok_or(RuntimeError::Overflow(span))
+        }
+        (Value::Float(lhs), Value::Float(rhs)) => Ok(float(lhs, op, rhs)),
+        (Value::Int(lhs), Value::Float(rhs)) => Ok(float(lhs as f64, op, rhs)),
+        (Value::Float(lhs), Value::Int(rhs)) => Ok(float(lhs, op, rhs as f64)),
+        (Value::String(lhs), Value::String(rhs)) => match op {
+            Operation::Add => Ok(Value::String(lhs + &rhs)),
+            Operation::EqEq => Ok(Value::Bool(lhs == rhs)),
+            op => Err(RuntimeError::Type(
+                format!("cannot apply `{op}` to string and string"),
+                span,
+            )),
+        },
+        (Value::Bool(lhs), Value::Bool(rhs)) if op == Operation::EqEq => {
+            Ok(Value::Bool(lhs == rhs))
+        }
+        (lhs, rhs) => Err(RuntimeError::Type(
+            format!(
+                "cannot apply `{op}` to {} and {}",
+                lhs.type_name(),
+                rhs.type_name()
+            ),
+            span,
+        )),
+    }
+}
+
+fn unary(op: UnaryOp, value: Value, span: Span) -> EvalResult {
+    match (op, value) {
+        (UnaryOp::Neg, Value::Int(int)) => int
+            .checked_neg()
+            .map(Value::Int)
+            .ok_or(RuntimeError::Overflow(span)),
+        (UnaryOp::Neg, Value::Float(float)) => Ok(Value::Float(-float)),
+        (UnaryOp::Not, Value::Bool(bool)) => Ok(Value::Bool(!bool)),
+        (op, value) => Err(RuntimeError::Type(
+            format!("cannot apply `{op}` to {}", value.type_name()),
+            span,
+        )),
+    }
+}
+
+fn compare<T: PartialOrd>(lhs: T, op: Operation, rhs: T) -> bool {
+    match op {
+        Operation::EqEq => lhs == rhs,
+        Operation::Less => lhs < rhs,
+        Operation::LessEq => lhs <= rhs,
+        Operation::More => lhs > rhs,
+        Operation::MoreEq => lhs >= rhs,
+        Operation::Add | Operation::Sub | Operation::Mul | Operation::Div => {
+            unreachable!("Arithmetic is not a comparison.")
+        }
+    }
+}
+
+fn float(lhs: f64, op: Operation, rhs: f64) -> Value {
+    match op {
+        Operation::Add => Value::Float(lhs + rhs),
+        Operation::Sub => Value::Float(lhs - rhs),
+        Operation::Mul => Value::Float(lhs * rhs),
+        Operation::Div => Value::Float(lhs / rhs),
+        op => Value::Bool(compare(lhs, op, rhs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        eval::{Interpreter, RuntimeError},
+        value::Value,
+    };
+    use ast::{Expr, ExprKind, Mutability, Operation, Span, Stmt, StmtKind, StringPart, ValueKind};
+    use parser::{lexer::Lexer, Parser};
+    use std::{cell::RefCell, collections::HashMap, io, rc::Rc};
+
+    fn value(kind: ValueKind) -> Expr {
+        ast::Value::new(kind, Span::default()).into()
+    }
+
+    fn run(source: &str) -> Result<Value, RuntimeError> {
+        let ast = Parser::from_lexer(Lexer::new(source)).parse().unwrap();
+        Interpreter::new().run(&ast)
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let mut interpreter = Interpreter::new();
+        let name = Stmt::new(
+            StmtKind::Let(
+                "name".into(),
+                Mutability::Not,
+                None,
+                value(ValueKind::String("monk".into())),
+            ),
+            Span::default(),
+        );
+        interpreter.exec(&name).unwrap();
+
+        let age = Expr::new(
+            ExprKind::Binary(
+                value(ValueKind::Int(1)).into(),
+                Operation::Add,
+                value(ValueKind::Int(2)).into(),
+            ),
+            Span::default(),
+        );
+        let interpolation = Expr::new(
+            ExprKind::Interpolation(vec![
+                StringPart::Literal("hello ".into()),
+                StringPart::Expr(value(ValueKind::Ident("name".into()))),
+                StringPart::Literal(", you are ".into()),
+                StringPart::Expr(age),
+                StringPart::Literal(" and ".into()),
+                StringPart::Expr(value(ValueKind::Float(2.0))),
+            ]),
+            Span::default(),
+        );
+        assert_eq!(
+            interpreter.eval(&interpolation),
+            Ok(Value::String("hello monk, you are 3 and 2.0".into()))
+        );
+    }
+
+    #[test]
+    fn test_if() {
+        let source = "let x = 5
+let size = if x < 3 { \"small\" } else if x < 10 { \"medium\" } else { \"large\" }
+size";
+        assert_eq!(run(source), Ok(Value::String("medium".into())));
+        assert_eq!(run("if 1 == 2 { 3 }"), Ok(Value::Unit));
+        assert_eq!(run("if 1 == 1 { }"), Ok(Value::Unit));
+
+        // Bindings made in a branch end with it, and shadowing doesn't leak out.
+        assert_eq!(
+            run("let a = 1\nif a == 1 { let a = 2 }\na"),
+            Ok(Value::Int(1))
+        );
+        assert!(matches!(
+            run("if 1 == 1 { let b = 2 }\nb"),
+            Err(RuntimeError::UnboundVariable(..))
+        ));
+        assert!(matches!(run("if 1 { 2 }"), Err(RuntimeError::Type(..))));
+
+        assert_eq!(run("if true { 1 } else { 2 }"), Ok(Value::Int(1)));
+        assert_eq!(
+            run("let b = !false
+b == true"),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            run("match 1 > 2 { true => \"yes\", false => \"no\" }"),
+            Ok(Value::String("no".into()))
+        );
+    }
+
+    #[test]
+    fn test_while() {
+        assert_eq!(run("while 1 > 2 { 1 / 0 }"), Ok(Value::Unit));
+        assert_eq!(
+            run("while 1 < 2 {\n if 2 > 1 { break }\n 1 / 0\n}"),
+            Ok(Value::Unit)
+        );
+        assert!(matches!(run("while 1 { 2 }"), Err(RuntimeError::Type(..))));
+        assert!(matches!(run("break"), Err(RuntimeError::Break(..))));
+    }
+
+    #[test]
+    fn test_match() {
+        let classify = |n: i64| {
+            run(&format!(
+                "match ({n}, {n} * 2) {{
+  (0, _) => \"zero\"
+  (n, _) if n < 0 => \"negative\"
+  (1 ..= 9, double) | (double, 100) => \"{{double}}\"
+  _ => \"large\"
+}}"
+            ))
+        };
+        assert_eq!(classify(0), Ok(Value::String("zero".into())));
+        assert_eq!(classify(-3), Ok(Value::String("negative".into())));
+        assert_eq!(classify(4), Ok(Value::String("8".into())));
+        assert_eq!(classify(50), Ok(Value::String("50".into())));
+        assert_eq!(classify(51), Ok(Value::String("large".into())));
+
+        let source = "let xs = [1, 2, 3]
+match xs { [] => 0, [x] => x, [first, .., last] => first + last }";
+        assert_eq!(run(source), Ok(Value::Int(4)));
+        assert_eq!(
+            run("match \"a\" { \"a\" | \"b\" => 1, _ => 2 }"),
+            Ok(Value::Int(1))
+        );
+        assert_eq!(run("match () { () => 1 }"), Ok(Value::Int(1)));
+        assert_eq!(
+            run("let t = (1, 2.5)\nt"),
+            Ok(Value::Tuple(vec![Value::Int(1), Value::Float(2.5)]))
+        );
+
+        // Bindings don't outlive their arm.
+        assert!(matches!(
+            run("match 1 { x => x }\nx"),
+            Err(RuntimeError::UnboundVariable(..))
+        ));
+        assert_eq!(
+            run("match 5 { 0 .. 5 => 1 }"),
+            Err(RuntimeError::NoMatch(Span::new(6, 7, 1, 7)))
+        );
+        assert_eq!(
+            run("match 1 { 0 => 0, Point { x } => x, _ => 2 }"),
+            Err(RuntimeError::Unsupported(
+                "Struct pattern".into(),
+                Span::new(18, 29, 1, 19)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(run("let add = |a, b| a + b\nadd(1, 2)"), Ok(Value::Int(3)));
+        assert_eq!(
+            run("let twice(f, x) = f(f(x))\ntwice(|x: int| x * 3, 2)"),
+            Ok(Value::Int(18))
+        );
+        assert_eq!(
+            run("let adder(n) = |x| x + n\nlet add5 = adder(5)\nadd5(1)"),
+            Ok(Value::Int(6))
+        );
+        assert_eq!(run("(|x| x)(4)"), Ok(Value::Int(4)));
+        assert_eq!(
+            run("let fact(n) = if n < 2 { 1 } else { n * fact(n - 1) }\nfact(5)"),
+            Ok(Value::Int(120))
+        );
+        assert_eq!(
+            run("let fs = [|| 1, || 2]\nlet mut sum = 0\nfor f <= fs { sum += f() }\nsum"),
+            Ok(Value::Int(3))
+        );
+
+        // Variables are captured by reference, and functions see ones declared after them.
+        assert_eq!(
+            run("let mut n = 1\nlet get = || n\nn = 2\nget()"),
+            Ok(Value::Int(2))
+        );
+        assert_eq!(
+            run("let mut total = 0\nlet add(x) = { total += x }\nadd(5)\nadd(2)\ntotal"),
+            Ok(Value::Int(7))
+        );
+        assert_eq!(run("let a() = b()\nlet b() = 1\na()"), Ok(Value::Int(1)));
+        assert_eq!(
+            run("let counter() = { let mut n = 0; || { n += 1; n } }\nlet next = counter()\nnext()\nnext()"),
+            Ok(Value::Int(2))
+        );
+        assert_eq!(run("let f = |x| x\nf").unwrap().to_string(), "<fn>");
+
+        assert!(matches!(
+            run("let f(a) = a\nf(1, 2)"),
+            Err(RuntimeError::Type(..))
+        ));
+        assert!(matches!(run("1(2)"), Err(RuntimeError::Type(..))));
+        assert!(matches!(
+            run("let stop() = { break }\nwhile 1 < 2 { stop() }"),
+            Err(RuntimeError::OutsideLoop("break", _))
+        ));
+    }
+
+    #[test]
+    fn test_print() {
+        #[derive(Clone, Default)]
+        struct Output(Rc<RefCell<Vec<u8>>>);
+
+        impl io::Write for Output {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Output::default();
+        let source = "let x = 3\nprint(\"x is\", x)\nprint()\nlet p = print\np([1, 2])";
+        let ast = Parser::from_lexer(Lexer::new(source)).parse().unwrap();
+        let value = Interpreter::with_output(output.clone()).run(&ast);
+        assert_eq!(value, Ok(Value::Unit));
+        assert_eq!(output.0.borrow().as_slice(), b"x is 3\n\n[1, 2]\n");
+
+        // A variable of the same name hides it.
+        assert_eq!(run("let print(x) = x\nprint(1)"), Ok(Value::Int(1)));
+        assert!(matches!(
+            run("print = 1"),
+            Err(RuntimeError::UnboundVariable(..))
+        ));
+    }
+
+    #[test]
+    fn test_release() {
+        // Returns the scope `source` ran in, once it has been left, and the value of `keep`.
+        let scope = |source: &str, keep: &str| {
+            let ast = Parser::from_lexer(Lexer::new(source)).parse().unwrap();
+            let mut interpreter = Interpreter::new();
+            let (scope, kept) = interpreter.scoped(HashMap::new(), |interpreter| {
+                interpreter.run(&ast).unwrap();
+                let scope = Rc::downgrade(interpreter.scopes.last().unwrap());
+                (scope, interpreter.lookup(&keep.into()))
+            });
+            let value = kept.map(|kept| {
+                let ast = Parser::from_lexer(Lexer::new("kept()")).parse().unwrap();
+                interpreter.declare("kept".into(), kept);
+                interpreter.run(&ast).unwrap()
+            });
+            (scope.strong_count(), value)
+        };
+        // Functions declared in it don't keep it alive.
+        assert_eq!(
+            scope(
+                "let n = 1
+let f() = n
+let g = || f()",
+                ""
+            ),
+            (0, None)
+        );
+        assert_eq!(scope("let fs = [|| 1]", ""), (0, None));
+        // Unless they can still be called.
+        assert_eq!(
+            scope(
+                "let n = 1
+let f() = n",
+                "f"
+            ),
+            (1, Some(Value::Int(1)))
+        );
+
+        // Neither do functions declared in loops and calls, which would also hold on to the
+        // scopes around them.
+        let source = "let mut i = 0
+let f() = { let g() = i\ng() }
+while i < 3 { let h() = f()\ni += h() + 1 }
+i";
+        let ast = Parser::from_lexer(Lexer::new(source)).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.run(&ast), Ok(Value::Int(3)));
+        // The interpreter's reference, and `f`'s.
+        assert_eq!(Rc::strong_count(&interpreter.scopes[0]), 2);
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(
+            run("let x = {\n  let y = 2\n  y * 3\n}\nx"),
+            Ok(Value::Int(6))
+        );
+        assert_eq!(run("{ { 1 } } + { 2 }"), Ok(Value::Int(3)));
+        assert_eq!(run("{}"), Ok(Value::Unit));
+
+        // Ending in a statement makes the block unit.
+        assert_eq!(run("let u = { let a = 1 }\nu"), Ok(Value::Unit));
+        assert_eq!(run("let mut i = 0\n{ i += 1 }"), Ok(Value::Unit));
+        assert_eq!(run("{ while 1 > 2 { } }"), Ok(Value::Unit));
+        assert_eq!(
+            run("if 1 == 1 { let a = 1; a + 1 } else { 0 }"),
+            Ok(Value::Int(2))
+        );
+
+        assert!(matches!(
+            run("{ let a = 1 }\na"),
+            Err(RuntimeError::UnboundVariable(..))
+        ));
+        assert_eq!(run("let mut a = 1\n{ let a = 5; a }\na"), Ok(Value::Int(1)));
+    }
+
+    #[test]
+    fn test_assignment() {
+        let source = "let mut i = 0
+let mut sum = 0
+while i < 10 {
+    i += 1
+    if i == 5 { continue }
+    sum += i
+}
+sum";
+        assert_eq!(run(source), Ok(Value::Int(50)));
+        assert_eq!(
+            run("let mut product = 1\nfor x <= 1 ..= 5 { product *= x }\nproduct"),
+            Ok(Value::Int(120))
+        );
+
+        // Assignment changes the innermost variable of that name.
+        assert_eq!(
+            run("let mut x = 1\nif x == 1 { x = 2 }\nx"),
+            Ok(Value::Int(2))
+        );
+        assert_eq!(
+            run("let mut x = 1\nif x == 1 { let mut x = 5; x -= 2 }\nx"),
+            Ok(Value::Int(1))
+        );
+        assert!(matches!(
+            run("y = 1"),
+            Err(RuntimeError::UnboundVariable(..))
+        ));
+        assert!(matches!(
+            run("let mut s = \"a\"\ns /= 2"),
+            Err(RuntimeError::Type(..))
+        ));
+    }
+
+    #[test]
+    fn test_for() {
+        // A division by zero shows which iterations ran.
+        assert_eq!(
+            run("for (x <= 0 .. 3) { if x == 3 { 1 / 0 } }"),
+            Ok(Value::Unit)
+        );
+        assert!(matches!(
+            run("for x <= 3 ..= 0 step 2 { if x == 1 { 1 / 0 } }"),
+            Err(RuntimeError::DivisionByZero(..))
+        ));
+        assert_eq!(
+            run("for x <= [1, 2, 3] { if x == 2 { break }\n 1 / (x - 2) }"),
+            Ok(Value::Unit)
+        );
+        assert!(matches!(
+            run("for c <= \"ab\" { if c == \"b\" { 1 / 0 } }"),
+            Err(RuntimeError::DivisionByZero(..))
+        ));
+
+        assert_eq!(run("[1, 2 + 3]").unwrap().to_string(), "[1, 5]");
+        assert_eq!(run("0 ..= 10 step 5").unwrap().to_string(), "0..=10 step 5");
+        assert!(matches!(run("0 .. 1 step 0"), Err(RuntimeError::Type(..))));
+        assert!(matches!(run("for x <= 1 { }"), Err(RuntimeError::Type(..))));
+        assert!(matches!(
+            run("for x <= [1] { }\nx"),
+            Err(RuntimeError::UnboundVariable(..))
+        ));
+    }
+}
@@ -1 +1,4 @@
+pub mod check;
+pub mod eval;
 pub mod term;
+pub mod value;
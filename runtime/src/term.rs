@@ -1,5 +1,7 @@
+use ast::Symbol;
+
 pub enum Term {
-    Var(String),
-    Abstr(String, Box<Term>),
+    Var(Symbol),
+    Abstr(Symbol, Box<Term>),
     Apply(Box<Term>, Box<Term>),
 }
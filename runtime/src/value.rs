@@ -0,0 +1,186 @@
+use ast::{Expr, Symbol};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// The variables of one scope. Shared, so functions made in it see it as it changes.
+pub type Scope = Rc<RefCell<HashMap<Symbol, Value>>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Unit,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    List(Vec<Value>),
+    /// Never empty, an empty tuple is `Unit`.
+    Tuple(Vec<Value>),
+    Range(Range),
+    Func(Rc<Closure>),
+    Builtin(Builtin),
+}
+
+/// A function that comes with the language. Names of variables take precedence over them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Builtin {
+    /// Writes its arguments on a line, separated by spaces.
+    Print,
+}
+
+impl Builtin {
+    pub fn named(name: &Symbol) -> Option<Self> {
+        match name.as_str() {
+            "print" => Some(Builtin::Print),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::Print => "print",
+        }
+    }
+}
+
+/// A function together with the scopes it was made in. They are captured by reference, so the
+/// function sees later assignments and declarations, and can assign to them itself.
+#[derive(Debug)]
+pub struct Closure {
+    /// Set for `let name(..) = ..` functions, which can call themselves by it.
+    pub name: Option<Symbol>,
+    pub params: Vec<Symbol>,
+    pub body: Expr,
+    pub env: Vec<Scope>,
+}
+
+/// Functions are only equal to themselves.
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// An int range. It counts down when `end` is below `start`; `step` is always positive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub inclusive: bool,
+    pub step: i64,
+}
+
+impl Range {
+    pub fn iter(self) -> impl Iterator<Item = i64> {
+        let descending = self.end < self.start;
+        let mut next = Some(self.start);
+        std::iter::from_fn(move || {
+            let current = next?;
+            let within = match (descending, self.inclusive) {
+                (false, true) => current <= self.end,
+                (false, false) => current < self.end,
+                (true, true) => current >= self.end,
+                (true, false) => current > self.end,
+            };
+            if !within {
+                return None;
+            }
+            next = match descending {
+                true => current.checked_sub(self.step),
+                false => current.checked_add(self.step),
+            };
+            Some(current)
+        })
+    }
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Unit => "unit",
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Tuple(_) => "tuple",
+            Value::Range(_) => "range",
+            Value::Func(_) | Value::Builtin(_) => "function",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Unit => write!(f, "()"),
+            Value::Int(int) => write!(f, "{int}"),
+            // Debug keeps the fractional part of whole floats, so `1.0` doesn't print as an int.
+            Value::Float(float) => write!(f, "{float:?}"),
+            Value::Bool(bool) => write!(f, "{bool}"),
+            Value::String(string) => write!(f, "{string}"),
+            Value::List(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Tuple(values) => {
+                write!(f, "(")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                match values.len() {
+                    1 => write!(f, ",)"),
+                    _ => write!(f, ")"),
+                }
+            }
+            Value::Range(range) => {
+                let dots = if range.inclusive { "..=" } else { ".." };
+                write!(f, "{}{dots}{}", range.start, range.end)?;
+                if range.step != 1 {
+                    write!(f, " step {}", range.step)?;
+                }
+                Ok(())
+            }
+            Value::Func(func) => match &func.name {
+                Some(name) => write!(f, "<fn {name}>"),
+                None => write!(f, "<fn>"),
+            },
+            Value::Builtin(builtin) => write!(f, "<fn {}>", builtin.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::value::Range;
+
+    #[test]
+    fn test_range() {
+        let range = |start, end, inclusive, step| {
+            let range = Range {
+                start,
+                end,
+                inclusive,
+                step,
+            };
+            range.iter().collect::<Vec<_>>()
+        };
+        assert_eq!(range(0, 3, false, 1), vec![0, 1, 2]);
+        assert_eq!(range(0, 3, true, 1), vec![0, 1, 2, 3]);
+        assert_eq!(range(0, 10, true, 5), vec![0, 5, 10]);
+        assert_eq!(range(3, 0, false, 1), vec![3, 2, 1]);
+        assert_eq!(range(10, 0, true, 4), vec![10, 6, 2]);
+        assert_eq!(range(1, 1, false, 1), Vec::<i64>::new());
+        assert_eq!(range(i64::MAX - 1, i64::MAX, true, 1).len(), 2);
+    }
+}
@@ -1,3 +1,58 @@
-fn main() {
-    println!("Hello, world!");
+use ast::Diagnostic;
+use parser::{lexer::Lexer, Parser};
+use runtime::{eval::Interpreter, value::Value};
+use std::io::{self, IsTerminal};
+use std::{env, fs, process::ExitCode};
+
+fn main() -> ExitCode {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("usage: monk <file>");
+        return ExitCode::FAILURE;
+    };
+    let source = match fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("{path}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let report = |diagnostic: Diagnostic| {
+        let color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+        eprintln!("{}", diagnostic.render(&source, &path, color));
+    };
+
+    let ast = match Parser::from_lexer(Lexer::new(&source))
+        .with_path(&path)
+        .parse()
+    {
+        Ok(ast) => ast,
+        Err(errors) => {
+            for err in errors {
+                report(err.diagnostic());
+            }
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let errors = runtime::check::check(&ast);
+    if !errors.is_empty() {
+        for err in errors {
+            report(err.diagnostic());
+        }
+        return ExitCode::FAILURE;
+    }
+
+    match Interpreter::new().run(&ast) {
+        Ok(value) => {
+            // Programs that print their output end in `()`, which isn't worth showing.
+            if value != Value::Unit {
+                println!("{value}");
+            }
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            report(err.diagnostic());
+            ExitCode::FAILURE
+        }
+    }
 }